@@ -0,0 +1,437 @@
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    thread,
+};
+
+use chrono::{DateTime, Utc};
+use faramir::{
+    errors::{AppError, AppResult},
+    filter::TimerFilter,
+    models::config::Config,
+    StartOptions, Store,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    output::{StartOutput, TimerOutput},
+    overlap,
+    table::Palette,
+};
+
+const SOCKET_NAME: &str = "faramir.sock";
+
+// JSON-RPC 2.0 error codes. The last two are ours: a failed command, and a
+// command that had nothing to do, like `stop` with several running timers.
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const COMMAND_FAILED: i64 = -32000;
+const NOTHING_TO_DO: i64 = -32001;
+
+/// Where the daemon listens, in `data_dir` next to the database.
+pub fn socket_path(config: &Config) -> PathBuf {
+    config.data_dir.join(SOCKET_NAME)
+}
+
+/// The params of `start`. `at` defaults to now, and with `keep` the project
+/// and tags are copied from the last timer.
+#[derive(Deserialize, Serialize)]
+pub struct StartParams {
+    pub project:       Option<String>,
+    #[serde(default)]
+    pub tags:          Vec<String>,
+    pub note:          Option<String>,
+    pub at:            Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub keep:          bool,
+    #[serde(default)]
+    pub switch:        bool,
+    #[serde(default)]
+    pub allow_overlap: bool,
+}
+
+/// The params of `stop`. Without `id` or `all`, only a single running timer
+/// is stopped.
+#[derive(Deserialize, Serialize)]
+pub struct StopParams {
+    pub id:  Option<String>,
+    #[serde(default)]
+    pub all: bool,
+    pub at:  Option<DateTime<Utc>>,
+}
+
+/// What `stop` did.
+pub enum Stopped {
+    Timers(Vec<TimerOutput>),
+    /// Nothing was stopped, for this reason.
+    Nothing(String),
+}
+
+/// Where `start`, `stop`, `status` and `log` run: on the database itself,
+/// or on a running daemon that has it open.
+pub enum Backend<'a> {
+    Direct(&'a mut Store),
+    Daemon(Client),
+}
+
+impl Backend<'_> {
+    /// Starts a timer. Run directly, overlaps are resolved interactively;
+    /// the daemon refuses them unless `allow_overlap` is set.
+    pub fn start(
+        &mut self, config: &Config, params: &StartParams,
+    ) -> AppResult<StartOutput> {
+        match self {
+            Backend::Direct(store) => {
+                let exempt: Vec<i32> = match params.switch {
+                    true => store.running()?.0.iter().map(|t| t.id).collect(),
+                    false => vec![],
+                };
+                overlap::save(
+                    store,
+                    config,
+                    params.at.unwrap_or_else(Utc::now),
+                    None,
+                    &exempt,
+                    params.allow_overlap,
+                    |store, at, _, allow_overlap| {
+                        start(store, params, at, allow_overlap)
+                    },
+                )
+            },
+            Backend::Daemon(client) => Ok(client.call("start", params)?),
+        }
+    }
+
+    pub fn stop(&mut self, params: &StopParams) -> AppResult<Stopped> {
+        match self {
+            Backend::Direct(store) => stop(store, params),
+            Backend::Daemon(client) => match client.call("stop", params) {
+                Ok(timers) => Ok(Stopped::Timers(timers)),
+                Err(err) if err.code == NOTHING_TO_DO => {
+                    Ok(Stopped::Nothing(err.message))
+                },
+                Err(err) => Err(err.into()),
+            },
+        }
+    }
+
+    /// The running timers.
+    pub fn status(&mut self) -> AppResult<Vec<TimerOutput>> {
+        match self {
+            Backend::Direct(store) => {
+                TimerOutput::load_all(store, store.running()?)
+            },
+            Backend::Daemon(client) => Ok(client.call("status", &())?),
+        }
+    }
+
+    pub fn log(&mut self, filter: &TimerFilter) -> AppResult<Vec<TimerOutput>> {
+        match self {
+            Backend::Direct(store) => {
+                TimerOutput::load_all(store, store.timers(filter)?)
+            },
+            Backend::Daemon(client) => Ok(client.call("log", filter)?),
+        }
+    }
+
+    pub fn palette(&mut self) -> AppResult<Palette> {
+        match self {
+            Backend::Direct(store) => Palette::load(store),
+            Backend::Daemon(client) => Ok(client.call("palette", &())?),
+        }
+    }
+}
+
+fn start(
+    store: &mut Store, params: &StartParams, at: DateTime<Utc>,
+    allow_overlap: bool,
+) -> AppResult<StartOutput> {
+    let (project, tags) = match (&params.project, params.keep) {
+        (_, true) => {
+            let last = store.edit_timer(&store.last_timer()?.rid)?;
+            (last.project, last.tags)
+        },
+        (Some(project), false) => (project.clone(), params.tags.clone()),
+        (None, false) => {
+            return Err(AppError::from_str("Please specify a project name."))
+        },
+    };
+
+    let (started, stopped) = store.start(
+        &project,
+        &tags,
+        params.note.clone(),
+        at,
+        StartOptions {
+            switch: params.switch,
+            allow_overlap,
+        },
+    )?;
+
+    Ok(StartOutput {
+        started: TimerOutput::load(store, started)?,
+        stopped: TimerOutput::load_all(store, stopped)?,
+    })
+}
+
+fn stop(store: &Store, params: &StopParams) -> AppResult<Stopped> {
+    let mut running = store.running()?;
+
+    if running.len() > 1 && !params.all {
+        match &params.id {
+            Some(rid) => {
+                running.0.retain(|t| &t.rid == rid);
+                if running.is_empty() {
+                    return Ok(Stopped::Nothing(
+                        "No currently running timer has that id.".into(),
+                    ));
+                }
+            },
+            None => {
+                return Ok(Stopped::Nothing(
+                    "Multiple timers are running. Specify a timer with -i \
+                     <id>."
+                        .into(),
+                ))
+            },
+        }
+    }
+
+    let at = params.at.unwrap_or_else(Utc::now);
+    for timer in &mut running.0 {
+        store.stop(timer, at)?;
+    }
+
+    Ok(Stopped::Timers(TimerOutput::load_all(store, running)?))
+}
+
+/// A JSON-RPC error object.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RpcError {
+    pub code:    i64,
+    pub message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: String) -> Self {
+        RpcError { code, message }
+    }
+}
+
+impl From<AppError> for RpcError {
+    fn from(err: AppError) -> Self {
+        RpcError::new(COMMAND_FAILED, err.to_string())
+    }
+}
+
+impl From<RpcError> for AppError {
+    fn from(err: RpcError) -> Self {
+        AppError::from_str(&err.message)
+    }
+}
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    #[serde(default)]
+    id:     Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse {
+    #[serde(default)]
+    result: Value,
+    error:  Option<RpcError>,
+}
+
+/// A connection to a running daemon.
+pub struct Client {
+    reader:  BufReader<UnixStream>,
+    writer:  UnixStream,
+    next_id: u64,
+}
+
+impl Client {
+    /// Connects to the daemon listening at `path`, if there is one.
+    pub fn connect(path: &Path) -> Option<Self> {
+        let stream = UnixStream::connect(path).ok()?;
+        let writer = stream.try_clone().ok()?;
+
+        Some(Client {
+            reader: BufReader::new(stream),
+            writer,
+            next_id: 1,
+        })
+    }
+
+    pub fn call<P: Serialize, R: DeserializeOwned>(
+        &mut self, method: &str, params: &P,
+    ) -> Result<R, RpcError> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": self.next_id,
+            "method": method,
+            "params": params,
+        });
+        self.next_id += 1;
+        writeln!(self.writer, "{}", request).map_err(AppError::from)?;
+
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).map_err(AppError::from)? == 0 {
+            return Err(AppError::from_str(
+                "The daemon closed the connection.",
+            )
+            .into());
+        }
+
+        let response: RpcResponse =
+            serde_json::from_str(&line).map_err(AppError::from)?;
+        match response.error {
+            Some(err) => Err(err),
+            None => Ok(serde_json::from_value(response.result)
+                .map_err(AppError::from)?),
+        }
+    }
+}
+
+/// Listens on the socket in `data_dir` until the process is stopped.
+///
+/// Every client gets a thread, and all of them share the one connection to
+/// the database, taking turns one request at a time.
+pub fn run(store: Store, config: Config) -> AppResult<()> {
+    let path = socket_path(&config);
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            return Err(AppError::from_str(&format!(
+                "A daemon is already listening on {}.",
+                path.display()
+            )));
+        }
+        // Left behind by a daemon that didn't shut down cleanly.
+        fs::remove_file(&path)?;
+    }
+
+    let listener = UnixListener::bind(&path)?;
+    println!("Listening on {}", path.display());
+
+    let store = Arc::new(Mutex::new(store));
+    let config = Arc::new(config);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("Unable to accept a client: {}", err);
+                continue;
+            },
+        };
+
+        let store = Arc::clone(&store);
+        let config = Arc::clone(&config);
+        thread::spawn(move || {
+            if let Err(err) = handle_client(stream, &store, &config) {
+                eprintln!("Lost a client: {}", err);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Answers each line the client sends with one line.
+fn handle_client(
+    stream: UnixStream, store: &Mutex<Store>, config: &Config,
+) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request: RpcRequest = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(err) => {
+                let err = RpcError::new(PARSE_ERROR, err.to_string());
+                writeln!(writer, "{}", response(Value::Null, Err(err)))?;
+                continue;
+            },
+        };
+
+        // Keep serving if another client's request panicked.
+        let mut store = store.lock().unwrap_or_else(PoisonError::into_inner);
+        let result =
+            dispatch(&mut store, config, &request.method, request.params);
+        drop(store);
+
+        writeln!(writer, "{}", response(request.id, result))?;
+    }
+
+    Ok(())
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    }
+}
+
+fn dispatch(
+    store: &mut Store, config: &Config, method: &str, params: Value,
+) -> Result<Value, RpcError> {
+    let result = match method {
+        "start" => {
+            let params: StartParams = parse_params(params)?;
+            let at = params.at.unwrap_or_else(Utc::now);
+            // The daemon can't ask what to do about an overlap.
+            let started = start(store, &params, at, params.allow_overlap)
+                .map_err(|err| overlap::explain(config, err))?;
+            serde_json::to_value(started)
+        },
+        "stop" => match stop(store, &parse_params(params)?)? {
+            Stopped::Timers(timers) => serde_json::to_value(timers),
+            Stopped::Nothing(reason) => {
+                return Err(RpcError::new(NOTHING_TO_DO, reason))
+            },
+        },
+        "status" => serde_json::to_value(TimerOutput::load_all(
+            store,
+            store.running()?,
+        )?),
+        "log" => {
+            let filter: TimerFilter = parse_params(params)?;
+            serde_json::to_value(TimerOutput::load_all(
+                store,
+                store.timers(&filter)?,
+            )?)
+        },
+        "palette" => serde_json::to_value(Palette::load(store)?),
+        _ => {
+            return Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method {}.", method),
+            ))
+        },
+    };
+
+    Ok(result.map_err(AppError::from)?)
+}
+
+/// Reads a request's params. Leaving them out is the same as `{}`.
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = match params {
+        Value::Null => json!({}),
+        params => params,
+    };
+
+    serde_json::from_value(params)
+        .map_err(|err| RpcError::new(INVALID_PARAMS, err.to_string()))
+}
@@ -1,151 +1,36 @@
-use std::io;
-
 use rusqlite::{params, Connection};
 
 use crate::{
     errors::*,
     models::{project::*, tag::*, timer::*},
-    utils,
+    query::{Order, TimerColumn, TimerQuery},
 };
 
-pub fn init_db(conn: &Connection) -> AppResult<usize> {
-    // projects
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS projects (
-            id INTEGER PRIMARY KEY,
-            name TEXT UNIQUE NOT NULL
-        );",
-        params![],
-    )?;
-
-    // tags
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS tags (
-            id INTEGER PRIMARY KEY,
-            name TEXT UNIQUE NOT NULL
-        );",
-        params![],
-    )?;
-
-    // timers
-    conn.execute(
-        "
-        CREATE TABLE IF NOT EXISTS timers (
-            id INTEGER PRIMARY KEY,
-            rid TEXT NOT NULL,
-            start TEXT NOT NULL,
-            end TEXT,
-            note TEXT
-        );",
-        params![],
-    )?;
-
-    // tags_timers
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS tags_timers (
-            tag_id INTEGER NOT NULL,
-            timer_id INTEGER NOT NULL,
-            FOREIGN KEY(tag_id) REFERENCES tags(id),
-            FOREIGN KEY(timer_id) REFERENCES timers(id)
-        );",
-        params![],
-    )?;
-
-    // projects_timers
-    let result = conn.execute(
-        "CREATE TABLE IF NOT EXISTS projects_timers (
-            project_id INTEGER NOT NULL,
-            timer_id INTEGER NOT NULL,
-            FOREIGN KEY(project_id) REFERENCES projects(id),
-            FOREIGN KEY(timer_id) REFERENCES timers(id)
-        );
-
-        CREATE UNIQUE INDEX projects_timers_idx
-        ON projects_timers (project_id, timer_id);
-        ",
-        params![],
-    );
-
-    result.map_err(|e| AppError::from(e))
-}
-
-pub fn delete_project(
-    conn: &mut Connection, name: &str, autoconfirm: bool,
-) -> AppResult<()> {
-    let project = match Project::find_by_name(&conn, &name) {
-        Ok(p) => p,
-        Err(e) => {
-            println!("Project not found.");
-            return Err(AppError::from(e));
-        },
-    };
+/// Removes a project along with its timers, returning how many timers were
+/// removed.
+pub fn delete_project(conn: &mut Connection, name: &str) -> AppResult<usize> {
+    let project = find_project(conn, name)?;
     let timers = Timers::for_project(&conn, project.id)?;
 
-    if timers.len() > 0 && autoconfirm != true {
-        println!(
-            "Project {} has {} timers associated with it. Are you sure you \
-             want to remove it?\nIf so, type 'y'.",
-            &name,
-            timers.len()
-        );
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-
-        if &input != "y\n" {
-            return Err(AppError::from_str(
-                "A confirmation with 'y' is needed to delete.",
-            ));
-        }
-    }
-
-    println!("deleting pt");
     conn.execute(
         "DELETE FROM projects_timers WHERE project_id = ?1",
         params![&project.id],
     )?;
 
-    println!("deleting projects");
     conn.execute("DELETE FROM projects WHERE id = ?1", params![&project.id])?;
 
-    println!("batch deleting");
+    let count = timers.len();
     timers.batch_delete(conn)?;
 
-    println!("Successfully removed project {}.", project.name);
-    Ok(())
+    Ok(count)
 }
 
-pub fn delete_tag(
-    conn: &Connection, name: &str, autoconfirm: bool,
-) -> AppResult<()> {
-    let tag = match Tag::find_by_name(&conn, &name) {
-        Ok(tag) => tag,
-        Err(e) => {
-            println!("Tag not found.");
-            return Err(AppError::from(e));
-        },
-    };
-
+/// Removes a tag from its timers and deletes it, returning how many timers
+/// had it.
+pub fn delete_tag(conn: &Connection, name: &str) -> AppResult<usize> {
+    let tag = find_tag(conn, name)?;
     let timers = Timers::for_tag(&conn, tag.id)?;
 
-    if timers.len() > 0 && autoconfirm != true {
-        println!(
-            "Tag {} has {} timers associated with it. Are you sure you want \
-             to remove it?\nIf so, type 'y'.",
-            &name,
-            timers.len()
-        );
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-
-        if &input != "y\n" {
-            return Err(AppError::from_str(
-                "A confirmation with 'y' is needed to delete.",
-            ));
-        }
-    }
-
     conn.execute(
         "DELETE FROM tags_timers WHERE tag_id = ?1",
         params![&tag.id],
@@ -153,46 +38,129 @@ pub fn delete_tag(
 
     conn.execute("DELETE FROM tags WHERE id = ?1", params![&tag.id])?;
 
-    println!("Succesfully removed tag {}.", &name);
-    Ok(())
+    Ok(timers.len())
 }
 
-pub fn delete_timer(conn: &Connection, rid: &str) -> AppResult<()> {
-    let timer = Timer::find_by(&conn, "rid", rid)?;
+pub fn delete_timer(conn: &mut Connection, rid: &str) -> AppResult<Timer> {
+    let timer = Timer::find_by(conn, TimerColumn::Rid, rid)?;
+    timer.delete(conn)?;
 
-    conn.execute(
-        "DELETE FROM projects_timers where timer_id = ?1",
-        params![&timer.id],
+    Ok(timer)
+}
+
+/// Moves every timer of project `from` to project `into`, then removes
+/// `from`. Returns how many timers were moved.
+pub fn merge_projects(
+    conn: &mut Connection, from: &str, into: &str,
+) -> AppResult<usize> {
+    if from == into {
+        return Err(AppError::from_str("Can't merge a project into itself."));
+    }
+
+    let from_project = find_project(conn, from)?;
+    let into_project = find_project(conn, into)?;
+    let timers = Timers::for_project(conn, from_project.id)?;
+
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM projects_timers WHERE project_id = ?1 AND timer_id IN \
+         (SELECT timer_id FROM projects_timers WHERE project_id = ?2)",
+        params![from_project.id, into_project.id],
+    )?;
+    tx.execute(
+        "UPDATE projects_timers SET project_id = ?2 WHERE project_id = ?1",
+        params![from_project.id, into_project.id],
     )?;
+    tx.execute("DELETE FROM projects WHERE id = ?1", params![from_project.id])?;
+    tx.commit()?;
 
-    conn.execute(
-        "DELETE FROM tags_timers where timer_id = ?1",
-        params![&timer.id],
+    Ok(timers.len())
+}
+
+/// Moves every timer tagged `from` to tag `into`, then removes `from`.
+/// Timers that already had both tags keep a single link. Returns how many
+/// timers had `from`.
+pub fn merge_tags(
+    conn: &mut Connection, from: &str, into: &str,
+) -> AppResult<usize> {
+    if from == into {
+        return Err(AppError::from_str("Can't merge a tag into itself."));
+    }
+
+    let from_tag = find_tag(conn, from)?;
+    let into_tag = find_tag(conn, into)?;
+    let timers = Timers::for_tag(conn, from_tag.id)?;
+
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM tags_timers WHERE tag_id = ?1 AND timer_id IN (SELECT \
+         timer_id FROM tags_timers WHERE tag_id = ?2)",
+        params![from_tag.id, into_tag.id],
+    )?;
+    tx.execute(
+        "UPDATE tags_timers SET tag_id = ?2 WHERE tag_id = ?1",
+        params![from_tag.id, into_tag.id],
     )?;
+    tx.execute("DELETE FROM tags WHERE id = ?1", params![from_tag.id])?;
+    tx.commit()?;
 
-    conn.execute("DELETE FROM timers where id = ?1", params![&timer.id])?;
+    Ok(timers.len())
+}
 
-    println!(
-        "Successfully deleted timer {} - start: {}, end: {:?}",
-        timer.rid, timer.start, timer.end
-    );
+/// Finds a project by name, with an error that names it if it's missing.
+pub fn find_project(conn: &Connection, name: &str) -> AppResult<Project> {
+    Project::find_by_name(conn, name).map_err(|_| {
+        AppError::not_found(&format!("Project {} not found.", name))
+    })
+}
 
-    Ok(())
+/// Finds a tag by name, with an error that names it if it's missing.
+pub fn find_tag(conn: &Connection, name: &str) -> AppResult<Tag> {
+    Tag::find_by_name(conn, name)
+        .map_err(|_| AppError::not_found(&format!("Tag {} not found.", name)))
 }
 
-pub fn handle_inserts(
-    conn: &mut Connection, project: &str, tag_str: Option<&str>,
-    create_timer: &CreateTimer,
-) -> AppResult<()> {
-    let project_id = Project::insert_and_get_id(&conn, project)?;
-    let tags = utils::parse_tags(tag_str);
+/// The project and tags of the most recently started timers, newest first,
+/// with repeated combinations left out.
+pub fn recent_combinations(
+    conn: &Connection, count: usize,
+) -> AppResult<Vec<(String, Vec<String>)>> {
+    let projects = Projects::names_by_timer(conn)?;
+    let mut tags = Tags::names_by_timer(conn)?;
+    let timers = TimerQuery::new()
+        .order_by(TimerColumn::Start, Order::Desc)
+        .fetch(conn)?;
+
+    let mut combinations = vec![];
+    for timer in timers.0 {
+        let project = match projects.get(&timer.id) {
+            Some(project) => project.clone(),
+            None => continue,
+        };
+        let combination = (project, tags.remove(&timer.id).unwrap_or_default());
+
+        if !combinations.contains(&combination) {
+            combinations.push(combination);
+        }
+        if combinations.len() == count {
+            break;
+        }
+    }
 
-    let tag_ids = match tags {
-        Some(tags) => Some(Tag::batch_insert(conn, tags)?),
-        None => None,
-    };
+    Ok(combinations)
+}
 
-    let timer_id = create_timer.insert_and_get_id(&conn)?;
+/// Inserts a timer with its project and tags, creating those that don't
+/// exist. Returns the id of the new timer. Callers run it in a transaction
+/// along with their checks, see `Store::add`.
+pub fn insert_timer(
+    conn: &Connection, project: &str, tags: &[String],
+    create_timer: &CreateTimer,
+) -> AppResult<i32> {
+    let project_id = Project::insert_and_get_id(conn, project)?;
+    let tag_ids = Tag::batch_insert(conn, tags)?;
+
+    let timer_id = create_timer.insert_and_get_id(conn)?;
 
     conn.execute(
         "INSERT OR IGNORE INTO projects_timers (project_id, timer_id) VALUES \
@@ -200,16 +168,47 @@ pub fn handle_inserts(
         params![project_id, timer_id],
     )?;
 
-    if let Some(tag_ids) = tag_ids {
-        let tx = conn.transaction()?;
-        for tag_id in tag_ids {
-            tx.execute(
-                "INSERT OR IGNORE INTO tags_timers (tag_id, timer_id) VALUES \
-                 (?1, ?2)",
-                &[tag_id, timer_id],
-            )?;
-        }
-        tx.commit()?;
+    for tag_id in tag_ids {
+        conn.execute(
+            "INSERT OR IGNORE INTO tags_timers (tag_id, timer_id) VALUES (?1, \
+             ?2)",
+            [tag_id, timer_id],
+        )?;
+    }
+
+    Ok(timer_id)
+}
+
+/// Overwrites a timer's times and note, and replaces its project and tags.
+/// Callers run it in a transaction along with their checks, see
+/// `Store::update_timer`.
+pub fn update_timer(
+    conn: &Connection, timer: &Timer, project: &str, tags: &[String],
+) -> AppResult<()> {
+    let project_id = Project::insert_and_get_id(conn, project)?;
+    let tag_ids = Tag::batch_insert(conn, tags)?;
+
+    timer.update(conn)?;
+
+    conn.execute(
+        "DELETE FROM projects_timers WHERE timer_id = ?1",
+        params![timer.id],
+    )?;
+    conn.execute(
+        "INSERT INTO projects_timers (project_id, timer_id) VALUES (?1, ?2)",
+        params![project_id, timer.id],
+    )?;
+
+    conn.execute(
+        "DELETE FROM tags_timers WHERE timer_id = ?1",
+        params![timer.id],
+    )?;
+    for tag_id in tag_ids {
+        conn.execute(
+            "INSERT OR IGNORE INTO tags_timers (tag_id, timer_id) VALUES \
+             (?1, ?2)",
+            params![tag_id, timer.id],
+        )?;
     }
 
     Ok(())
@@ -0,0 +1,32 @@
+use std::{env, fs, path::Path, process::Command};
+
+use faramir::{errors::AppResult, utils::rand_string};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Opens `value` as JSON in $EDITOR and returns the edited version. Returns
+/// `None` if $EDITOR isn't set.
+pub fn edit_json<T: Serialize + DeserializeOwned>(
+    data_dir: &Path, value: &T,
+) -> AppResult<Option<T>> {
+    let editor = match env::var("EDITOR") {
+        Ok(editor) => editor,
+        Err(_) => {
+            println!("Please set the EDITOR environment variable.");
+            return Ok(None);
+        },
+    };
+
+    let file_name = format!(".faramir-edit-{}.tmp.json", rand_string(5));
+    let tmp_file_path = data_dir.join(file_name);
+
+    let json = serde_json::to_string_pretty(value)?;
+    fs::write(&tmp_file_path, &json)?;
+
+    Command::new(editor).arg(&tmp_file_path).status()?;
+
+    let content = fs::read_to_string(&tmp_file_path)?;
+    let edited = serde_json::from_str(&content)?;
+    fs::remove_file(tmp_file_path)?;
+
+    Ok(Some(edited))
+}
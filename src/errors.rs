@@ -1,28 +1,49 @@
 use std::{fmt, result};
 
+use crate::models::timer::Timers;
+
 pub type AppResult<T> = result::Result<T, AppError>;
 
 pub struct AppError(Box<ErrorKind>);
 
 impl AppError {
-    pub(crate) fn new(kind: ErrorKind) -> AppError {
+    pub fn new(kind: ErrorKind) -> AppError {
         AppError(Box::new(kind))
     }
 
-    pub(crate) fn from_str(msg: &str) -> AppError {
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(msg: &str) -> AppError {
         AppError(Box::new(ErrorKind::Generic(msg.into())))
     }
 
-    pub(crate) fn kind(&self) -> &ErrorKind {
+    /// An error for a timer, project or tag that doesn't exist.
+    pub fn not_found(msg: &str) -> AppError {
+        AppError(Box::new(ErrorKind::NotFound(msg.into())))
+    }
+
+    /// An error for a timer that would overlap the existing `timers`.
+    pub fn overlap(timers: Timers) -> AppError {
+        AppError(Box::new(ErrorKind::Overlap(timers)))
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
         &self.0
     }
+
+    pub fn into_kind(self) -> ErrorKind {
+        *self.0
+    }
 }
 
 #[derive(Debug)]
 pub enum ErrorKind {
     Generic(String),
+    NotFound(String),
+    /// Saving a timer was refused because it overlaps these timers.
+    Overlap(Timers),
     IO(std::io::Error),
     SerdeJson(serde_json::error::Error),
+    Csv(csv::Error),
     Rusqlite(rusqlite::Error),
     ChronoParse(chrono::format::ParseError),
     StringParse(std::string::String),
@@ -32,8 +53,11 @@ impl fmt::Display for AppError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self.0 {
             ErrorKind::Generic(ref msg) => write!(f, "{}", msg),
+            ErrorKind::NotFound(ref msg) => write!(f, "{}", msg),
+            ErrorKind::Overlap(ref timers) => write_overlap(f, timers),
             ErrorKind::IO(ref err) => err.fmt(f),
             ErrorKind::SerdeJson(ref err) => err.fmt(f),
+            ErrorKind::Csv(ref err) => err.fmt(f),
             ErrorKind::Rusqlite(ref err) => err.fmt(f),
             ErrorKind::ChronoParse(ref err) => err.fmt(f),
             ErrorKind::StringParse(ref err) => err.fmt(f),
@@ -45,8 +69,14 @@ impl fmt::Debug for AppError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self.0 {
             ErrorKind::Generic(ref msg) => write!(f, "Faramir Error: {}", msg),
+            ErrorKind::NotFound(ref msg) => write!(f, "Faramir Error: {}", msg),
+            ErrorKind::Overlap(ref timers) => {
+                write!(f, "Faramir Error: ")?;
+                write_overlap(f, timers)
+            },
             ErrorKind::IO(ref err) => err.fmt(f),
             ErrorKind::SerdeJson(ref err) => err.fmt(f),
+            ErrorKind::Csv(ref err) => err.fmt(f),
             ErrorKind::Rusqlite(ref err) => err.fmt(f),
             ErrorKind::ChronoParse(ref err) => err.fmt(f),
             ErrorKind::StringParse(ref err) => err.fmt(f),
@@ -54,6 +84,16 @@ impl fmt::Debug for AppError {
     }
 }
 
+fn write_overlap(f: &mut fmt::Formatter, timers: &Timers) -> fmt::Result {
+    let rids: Vec<&str> = timers.0.iter().map(|t| t.rid.as_str()).collect();
+    write!(
+        f,
+        "This timer overlaps {} existing timer(s): {}.",
+        rids.len(),
+        rids.join(", ")
+    )
+}
+
 impl From<std::io::Error> for AppError {
     fn from(err: std::io::Error) -> AppError {
         AppError::new(ErrorKind::IO(err))
@@ -66,6 +106,12 @@ impl From<serde_json::error::Error> for AppError {
     }
 }
 
+impl From<csv::Error> for AppError {
+    fn from(err: csv::Error) -> AppError {
+        AppError::new(ErrorKind::Csv(err))
+    }
+}
+
 impl From<rusqlite::Error> for AppError {
     fn from(err: rusqlite::Error) -> AppError {
         AppError::new(ErrorKind::Rusqlite(err))
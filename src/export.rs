@@ -0,0 +1,174 @@
+use std::{io::Write, str::FromStr};
+
+use chrono::{
+    offset::{Offset, TimeZone},
+    DateTime, FixedOffset, Utc,
+};
+use chrono_tz::Tz;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{AppError, AppResult},
+    filter::TimerFilter,
+    models::{
+        project::Projects, segment::Segments, tag::Tags, timer::Timers,
+    },
+};
+
+pub const CSV_HEADERS: [&str; 8] = [
+    "rid", "start", "end", "duration", "note", "project", "tags", "segments",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Csv,
+    Json,
+    Jsonl,
+}
+
+impl FromStr for Format {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> AppResult<Self> {
+        match s {
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::Jsonl),
+            _ => Err(AppError::from_str(
+                "--format must be one of csv, json, jsonl.",
+            )),
+        }
+    }
+}
+
+/// A timer flattened together with its project and tag names, as it appears
+/// in an export file. `duration` is in seconds.
+///
+/// `segments` keep a paused timer's pauses across an export and import.
+/// Files without them, e.g. older exports or Watson frames, are imported
+/// as timers that ran from `start` to `end` without a pause.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ExportedTimer {
+    pub rid:      String,
+    pub start:    DateTime<FixedOffset>,
+    pub end:      Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub duration: i64,
+    pub note:     Option<String>,
+    pub project:  Option<String>,
+    #[serde(default)]
+    pub tags:     Vec<String>,
+    #[serde(default)]
+    pub segments: Vec<ExportedSegment>,
+}
+
+/// One uninterrupted stretch of an exported timer, see `Segment`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ExportedSegment {
+    pub start: DateTime<FixedOffset>,
+    pub end:   Option<DateTime<FixedOffset>>,
+}
+
+impl ExportedTimer {
+    /// Loads every timer matching `filter`. Times are rendered in `tz` when
+    /// given, and in UTC otherwise.
+    pub fn load(
+        conn: &Connection, filter: &TimerFilter, tz: Option<&Tz>,
+    ) -> AppResult<Vec<Self>> {
+        let timers = Timers::filter(conn, filter)?;
+        let mut project_names = Projects::names_by_timer(conn)?;
+        let mut tag_names = Tags::names_by_timer(conn)?;
+        let segments = Segments::by_timer(conn)?;
+
+        let mut exported = vec![];
+        for timer in timers.0 {
+            let timer_segments = segments.get(&timer.id);
+            exported.push(ExportedTimer {
+                duration: timer.duration_of(timer_segments).num_seconds(),
+                segments: timer_segments
+                    .map(|segments| {
+                        segments
+                            .0
+                            .iter()
+                            .map(|segment| ExportedSegment {
+                                start: render(segment.start, tz),
+                                end:   segment.end.map(|end| render(end, tz)),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                start:    render(timer.start, tz),
+                end:      timer.end.map(|end| render(end, tz)),
+                project:  project_names.remove(&timer.id),
+                tags:     tag_names.remove(&timer.id).unwrap_or_default(),
+                rid:      timer.rid,
+                note:     timer.note,
+            });
+        }
+
+        Ok(exported)
+    }
+
+    fn csv_record(&self) -> Vec<String> {
+        vec![
+            self.rid.clone(),
+            self.start.to_rfc3339(),
+            self.end.map(|end| end.to_rfc3339()).unwrap_or_default(),
+            self.duration.to_string(),
+            self.note.clone().unwrap_or_default(),
+            self.project.clone().unwrap_or_default(),
+            self.tags.join(","),
+            self.segments
+                .iter()
+                .map(|segment| {
+                    format!(
+                        "{}/{}",
+                        segment.start.to_rfc3339(),
+                        segment
+                            .end
+                            .map(|end| end.to_rfc3339())
+                            .unwrap_or_default()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(";"),
+        ]
+    }
+}
+
+pub fn write<W: Write>(
+    writer: &mut W, format: Format, timers: &[ExportedTimer],
+) -> AppResult<()> {
+    match format {
+        Format::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+            csv_writer.write_record(CSV_HEADERS)?;
+            for timer in timers {
+                csv_writer.write_record(timer.csv_record())?;
+            }
+            csv_writer.flush()?;
+        },
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *writer, timers)?;
+            writeln!(writer)?;
+        },
+        Format::Jsonl => {
+            for timer in timers {
+                serde_json::to_writer(&mut *writer, timer)?;
+                writeln!(writer)?;
+            }
+        },
+    }
+
+    Ok(())
+}
+
+fn render(dt: DateTime<Utc>, tz: Option<&Tz>) -> DateTime<FixedOffset> {
+    let offset = match tz {
+        Some(tz) => tz.offset_from_utc_datetime(&dt.naive_utc()).fix(),
+        None => FixedOffset::east(0),
+    };
+
+    dt.with_timezone(&offset)
+}
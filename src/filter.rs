@@ -0,0 +1,111 @@
+use chrono::{
+    offset::TimeZone, DateTime, Datelike, Duration, NaiveDate, Utc,
+};
+use chrono_tz::Tz;
+use clap::ArgMatches;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::{AppError, AppResult},
+    models::config::Config,
+    timeparse,
+};
+
+/// Which timers a command like `log` should operate on.
+///
+/// `from` is inclusive and `to` is exclusive, both compared against a
+/// timer's start time.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TimerFilter {
+    pub from:            Option<DateTime<Utc>>,
+    pub to:              Option<DateTime<Utc>>,
+    pub projects:        Vec<String>,
+    pub tags:            Vec<String>,
+    pub include_running: bool,
+    pub limit:           Option<i64>,
+}
+
+impl TimerFilter {
+    /// Builds a filter from the shared range arguments: `--from`, `--to`,
+    /// `--today`, `--yesterday`, `--week`, `--month`, `--year`, `--project`
+    /// and `--tag`.
+    pub fn from_matches(
+        config: &Config, sub_matches: &ArgMatches,
+    ) -> AppResult<Self> {
+        let tz = config.tz()?;
+        let mut filter = TimerFilter::default();
+
+        if let Some((from, to)) = period_range(&tz, sub_matches)? {
+            filter.from = Some(from);
+            filter.to = Some(to);
+        }
+
+        if let Some(from_str) = sub_matches.value_of("from") {
+            filter.from = Some(timeparse::parse_time(from_str, config)?);
+        }
+
+        if let Some(to_str) = sub_matches.value_of("to") {
+            filter.to = Some(timeparse::parse_time(to_str, config)?);
+        }
+
+        if let Some(projects) = sub_matches.values_of("project") {
+            filter.projects = projects.map(|p| p.into()).collect();
+        }
+
+        if let Some(tags) = sub_matches.values_of("tag") {
+            filter.tags = tags.map(|t| t.into()).collect();
+        }
+
+        Ok(filter)
+    }
+
+    pub fn has_range(&self) -> bool {
+        self.from.is_some() || self.to.is_some()
+    }
+}
+
+/// Returns the `[start, end)` range of the requested period flag, computed
+/// in the user's timezone.
+fn period_range(
+    tz: &Tz, sub_matches: &ArgMatches,
+) -> AppResult<Option<(DateTime<Utc>, DateTime<Utc>)>> {
+    let today = Utc::now().with_timezone(tz).date().naive_local();
+
+    let (start, end) = if sub_matches.is_present("today") {
+        (today, today.succ())
+    } else if sub_matches.is_present("yesterday") {
+        (today.pred(), today)
+    } else if sub_matches.is_present("week") {
+        let monday = today
+            - Duration::days(today.weekday().num_days_from_monday() as i64);
+        (monday, monday + Duration::days(7))
+    } else if sub_matches.is_present("month") {
+        let first = NaiveDate::from_ymd(today.year(), today.month(), 1);
+        let next = match today.month() {
+            12 => NaiveDate::from_ymd(today.year() + 1, 1, 1),
+            m => NaiveDate::from_ymd(today.year(), m + 1, 1),
+        };
+        (first, next)
+    } else if sub_matches.is_present("year") {
+        (
+            NaiveDate::from_ymd(today.year(), 1, 1),
+            NaiveDate::from_ymd(today.year() + 1, 1, 1),
+        )
+    } else {
+        return Ok(None);
+    };
+
+    Ok(Some((local_midnight(tz, start)?, local_midnight(tz, end)?)))
+}
+
+fn local_midnight(tz: &Tz, date: NaiveDate) -> AppResult<DateTime<Utc>> {
+    match tz.from_local_datetime(&date.and_hms(0, 0, 0)).earliest() {
+        Some(dt) => Ok(dt.with_timezone(&Utc)),
+        None => Err(AppError::from_str(&format!(
+            "Unable to compute midnight of {} in timezone {}.",
+            date,
+            tz.name()
+        ))),
+    }
+}
@@ -0,0 +1,377 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+
+use crate::{
+    db,
+    errors::{AppError, AppResult, ErrorKind},
+    export::{ExportedSegment, ExportedTimer, Format},
+    models::{
+        project::Project,
+        segment::{Bounds, Segments},
+        tag::Tags,
+        timer::{CreateTimer, Timer, Timers},
+    },
+    query::TimerColumn,
+    watson,
+};
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub inserted:        usize,
+    pub updated:         usize,
+    pub skipped:         usize,
+    /// The rids of skipped timers that have no project.
+    pub without_project: Vec<String>,
+    /// The rids of imported timers whose duration doesn't match their start
+    /// and end, but which come without segments to restore their pauses
+    /// from.
+    pub lost_pauses:     Vec<String>,
+}
+
+/// A row of a CSV export. Empty cells stand in for missing values.
+/// `segments` are `start/end` pairs separated by `;`.
+#[derive(Debug, Deserialize)]
+struct CsvRow {
+    rid:      String,
+    start:    String,
+    end:      String,
+    #[serde(default)]
+    duration: i64,
+    note:     String,
+    project:  String,
+    tags:     String,
+    #[serde(default)]
+    segments: String,
+}
+
+impl CsvRow {
+    fn into_exported(self) -> AppResult<ExportedTimer> {
+        let start = DateTime::parse_from_rfc3339(&self.start)?;
+        let end = match self.end.as_str() {
+            "" => None,
+            end => Some(DateTime::parse_from_rfc3339(end)?),
+        };
+
+        Ok(ExportedTimer {
+            rid: self.rid,
+            start,
+            end,
+            duration: self.duration,
+            note: non_empty(self.note),
+            project: non_empty(self.project),
+            tags: self
+                .tags
+                .split(',')
+                .filter(|t| !t.is_empty())
+                .map(|t| t.into())
+                .collect(),
+            segments: self
+                .segments
+                .split(';')
+                .filter(|s| !s.is_empty())
+                .map(parse_segment)
+                .collect::<AppResult<_>>()?,
+        })
+    }
+}
+
+fn parse_segment(segment: &str) -> AppResult<ExportedSegment> {
+    let mut bounds = segment.splitn(2, '/');
+    let start = DateTime::parse_from_rfc3339(bounds.next().unwrap_or(""))?;
+    let end = match bounds.next() {
+        None | Some("") => None,
+        Some(end) => Some(DateTime::parse_from_rfc3339(end)?),
+    };
+
+    Ok(ExportedSegment { start, end })
+}
+
+fn non_empty(s: String) -> Option<String> {
+    match s.is_empty() {
+        true => None,
+        false => Some(s),
+    }
+}
+
+/// Guesses the format of an export file from its extension, falling back to
+/// JSON.
+pub fn detect_format(path: &Path) -> Format {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => Format::Csv,
+        Some("jsonl") => Format::Jsonl,
+        _ => Format::Json,
+    }
+}
+
+pub fn read(path: &Path, format: Format) -> AppResult<Vec<ExportedTimer>> {
+    match format {
+        Format::Csv => {
+            let mut reader = csv::Reader::from_path(path)?;
+            let mut timers = vec![];
+            for row in reader.deserialize() {
+                let row: CsvRow = row?;
+                timers.push(row.into_exported()?);
+            }
+            Ok(timers)
+        },
+        Format::Json => {
+            let content = fs::read_to_string(path)?;
+            serde_json::from_str(&content).map_err(AppError::from)
+        },
+        Format::Jsonl => {
+            let content = fs::read_to_string(path)?;
+            content
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| serde_json::from_str(line).map_err(AppError::from))
+                .collect()
+        },
+    }
+}
+
+/// Inserts every timer whose rid isn't in the database yet. Timers that
+/// already exist are updated when `update` is set and skipped otherwise.
+/// With `dry_run`, nothing is written and the summary reports what would
+/// have changed.
+///
+/// The import runs in one transaction, so a timer that fails to import,
+/// e.g. because it overlaps another one without `allow_overlap`, leaves the
+/// database as it was.
+pub fn import_timers(
+    conn: &mut Connection, timers: Vec<ExportedTimer>, update: bool,
+    dry_run: bool, allow_overlap: bool,
+) -> AppResult<ImportSummary> {
+    let mut summary = ImportSummary::default();
+    let mut seen = HashMap::new();
+    let tx = conn.transaction()?;
+
+    for exported in timers {
+        let project = match &exported.project {
+            Some(project) => project.clone(),
+            None => {
+                summary.without_project.push(exported.rid);
+                summary.skipped += 1;
+                continue;
+            },
+        };
+        let start = exported.start.with_timezone(&Utc);
+        let end = exported.end.map(|end| end.with_timezone(&Utc));
+        let segments = segments(&exported)?;
+        let lost_pauses = match has_lost_pauses(&exported) {
+            true => Some(exported.rid.clone()),
+            false => None,
+        };
+
+        let existing =
+            optional(Timer::find_by(&tx, TimerColumn::Rid, &exported.rid))?;
+        // A dry run doesn't write the timers it inserts, so a rid that comes
+        // up again in `timers` is counted as the existing timer a real import
+        // would find by then.
+        if dry_run && existing.is_none() {
+            let rid = exported.rid.clone();
+            if let Some(previous) = seen.insert(rid, exported.clone()) {
+                match update && previous != exported {
+                    true => summary.updated += 1,
+                    false => summary.skipped += 1,
+                }
+                continue;
+            }
+        }
+
+        let state_timer = match existing {
+            Some(_) => None,
+            None => watson_state_timer(&tx, &exported)?,
+        };
+        // Watson has stopped a timer that an earlier import left running.
+        if let Some(mut timer) = state_timer {
+            if !allow_overlap {
+                Timers::check_overlaps(&tx, timer.start, end, Some(timer.id))?;
+            }
+            if !dry_run {
+                tx.execute(
+                    "UPDATE timers SET rid = ?2 WHERE id = ?1",
+                    params![timer.id, exported.rid],
+                )?;
+                timer.end = end;
+                timer.note = exported.note;
+                db::update_timer(&tx, &timer, &project, &exported.tags)?;
+                replace_segments(&tx, timer.id, &segments)?;
+            }
+            summary.updated += 1;
+            summary.lost_pauses.extend(lost_pauses);
+            continue;
+        }
+
+        match existing {
+            Some(mut timer) => {
+                if !update || is_unchanged(&tx, &timer, &exported)? {
+                    summary.skipped += 1;
+                    continue;
+                }
+
+                if !allow_overlap {
+                    Timers::check_overlaps(&tx, start, end, Some(timer.id))?;
+                }
+                if !dry_run {
+                    timer.start = start;
+                    timer.end = end;
+                    timer.note = exported.note;
+                    db::update_timer(&tx, &timer, &project, &exported.tags)?;
+                    replace_segments(&tx, timer.id, &segments)?;
+                }
+                summary.updated += 1;
+                summary.lost_pauses.extend(lost_pauses);
+            },
+            None => {
+                if !allow_overlap {
+                    Timers::check_overlaps(&tx, start, end, None)?;
+                }
+                if !dry_run {
+                    let create_timer = CreateTimer {
+                        rid: exported.rid,
+                        start,
+                        end,
+                        note: exported.note,
+                    };
+                    let timer_id = db::insert_timer(
+                        &tx,
+                        &project,
+                        &exported.tags,
+                        &create_timer,
+                    )?;
+                    replace_segments(&tx, timer_id, &segments)?;
+                }
+                summary.inserted += 1;
+                summary.lost_pauses.extend(lost_pauses);
+            },
+        }
+    }
+
+    tx.commit()?;
+    Ok(summary)
+}
+
+/// The exported segments in UTC, once they're known to be in order and
+/// within the timer.
+fn segments(exported: &ExportedTimer) -> AppResult<Vec<Bounds>> {
+    let invalid = || {
+        AppError::from_str(&format!(
+            "Timer {} has segments that are out of order or outside of it.",
+            exported.rid
+        ))
+    };
+
+    let mut segments = vec![];
+    let mut previous_end = Some(exported.start);
+    for segment in &exported.segments {
+        match previous_end {
+            Some(previous_end) if previous_end <= segment.start => {},
+            _ => return Err(invalid()),
+        }
+        match segment.end {
+            Some(end) if end < segment.start => return Err(invalid()),
+            _ => {},
+        }
+
+        previous_end = segment.end;
+        segments.push((
+            segment.start.with_timezone(&Utc),
+            segment.end.map(|end| end.with_timezone(&Utc)),
+        ));
+    }
+
+    let fits = match (previous_end, exported.end) {
+        (Some(last_end), Some(end)) => last_end <= end,
+        (None, Some(_)) => false,
+        (_, None) => true,
+    };
+    match segments.is_empty() || fits {
+        true => Ok(segments),
+        false => Err(invalid()),
+    }
+}
+
+/// Restores an imported timer's segments. Without any, the timer keeps the
+/// single segment `db::insert_timer` or `db::update_timer` fitted to it.
+fn replace_segments(
+    conn: &Connection, timer_id: i32, segments: &[Bounds],
+) -> AppResult<()> {
+    match segments.is_empty() {
+        true => Ok(()),
+        false => Segments::replace(conn, timer_id, segments),
+    }
+}
+
+/// Whether a timer's `duration` says it was paused, while it comes without
+/// the segments to tell when.
+fn has_lost_pauses(exported: &ExportedTimer) -> bool {
+    match exported.end {
+        Some(end) if exported.segments.is_empty() && exported.duration != 0 => {
+            exported.duration
+                != end.signed_duration_since(exported.start).num_seconds()
+        },
+        _ => false,
+    }
+}
+
+/// The timer an earlier import created from Watson's `state`, when
+/// `exported` is the frame Watson saved once it stopped that timer. Both
+/// have to start at the same time on the same project, and the frame's rid
+/// must not be in the database yet.
+fn watson_state_timer(
+    conn: &Connection, exported: &ExportedTimer,
+) -> AppResult<Option<Timer>> {
+    let rid = watson::state_rid(&exported.start);
+    let timer = match optional(Timer::find_by(conn, TimerColumn::Rid, &rid))? {
+        Some(timer) => timer,
+        None => return Ok(None),
+    };
+    let project = optional(Project::for_timer(conn, timer.id))?;
+
+    match timer.start == exported.start
+        && project.map(|p| p.name) == exported.project
+    {
+        true => Ok(Some(timer)),
+        false => Ok(None),
+    }
+}
+
+/// Turns a lookup that found no rows into `None`, keeping other errors.
+fn optional<T>(result: AppResult<T>) -> AppResult<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(err) => match err.kind() {
+            ErrorKind::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => {
+                Ok(None)
+            },
+            _ => Err(err),
+        },
+    }
+}
+
+fn is_unchanged(
+    conn: &Connection, timer: &Timer, exported: &ExportedTimer,
+) -> AppResult<bool> {
+    let project = optional(Project::for_timer(conn, timer.id))?;
+    let mut tags = Tags::for_timer(conn, timer.id)?.names();
+    let mut exported_tags = exported.tags.clone();
+    tags.sort();
+    exported_tags.sort();
+    let segments = Segments::for_timer(conn, timer.id)?;
+    let same_segments = exported.segments.is_empty()
+        || (segments.len() == exported.segments.len()
+            && segments.0.iter().zip(&exported.segments).all(|(s, e)| {
+                s.start == e.start
+                    && s.end == e.end.map(|end| end.with_timezone(&Utc))
+            }));
+
+    Ok(timer.start == exported.start
+        && timer.end == exported.end.map(|end| end.with_timezone(&Utc))
+        && timer.note == exported.note
+        && project.map(|p| p.name) == exported.project
+        && tags == exported_tags
+        && same_segments)
+}
@@ -0,0 +1,36 @@
+//! faramir's data layer: timers, projects and tags in a SQLite database.
+//!
+//! [`Store`] is the entry point. It opens a database, migrating it if
+//! needed, and returns data rather than printing it; the `faramir` binary is
+//! one consumer of it.
+//!
+//! ```no_run
+//! use chrono::Utc;
+//! use faramir::{filter::TimerFilter, StartOptions, Store};
+//!
+//! let mut store = Store::open("faramir.db".as_ref())?;
+//! let (timer, _) =
+//!     store.start("book", &[], None, Utc::now(), StartOptions::default())?;
+//! store.stop(&mut store.timer(&timer.rid)?, Utc::now())?;
+//!
+//! for timer in store.timers(&TimerFilter::default())?.0 {
+//!     println!("{} {}", timer.rid, timer.start);
+//! }
+//! # Ok::<(), faramir::errors::AppError>(())
+//! ```
+
+mod db;
+pub mod errors;
+pub mod export;
+pub mod filter;
+pub mod import;
+mod migrations;
+pub mod models;
+mod query;
+pub mod report;
+pub mod store;
+pub mod timeparse;
+pub mod utils;
+pub mod watson;
+
+pub use store::{StartOptions, Store};
@@ -1,22 +1,43 @@
-use std::{env, fs, fs::File, io::BufWriter, path::PathBuf, process::Command};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    thread, time,
+};
 
-use chrono::{offset::TimeZone, Utc};
+use chrono::Utc;
 use chrono_tz::Tz;
 use clap::{load_yaml, App, ArgMatches, Shell};
-use rusqlite::Connection;
-
-mod db;
-mod errors;
-mod models;
-mod utils;
-
-use errors::{AppError, AppResult, ErrorKind};
-use models::{
-    config::Config,
-    project::{Project, Projects},
-    tag::{Tag, Tags},
-    timer::{CreateTimer, Timer, Timers},
+use faramir::{
+    errors::{AppError, AppResult, ErrorKind},
+    export,
+    filter::TimerFilter,
+    import,
+    models::{
+        config::Config,
+        project::Projects,
+        timer::{CreateTimer, Timer},
+    },
+    report::{GroupBy, Report},
+    timeparse, utils, watson, Store,
 };
+use serde::Serialize;
+use termcolor::Color;
+
+mod daemon;
+mod editor;
+mod output;
+mod overlap;
+mod serve;
+mod statusbar;
+mod table;
+
+use daemon::{Backend, Client, StartParams, StopParams, Stopped};
+use output::{Output, Removed, TimerOutput};
+use statusbar::BarFormat;
+use table::{name_color, project_cell, Cell, Palette, Table};
 
 fn load_or_create_config(config_path: PathBuf) -> AppResult<Config> {
     match Config::from_path(&config_path) {
@@ -28,7 +49,9 @@ fn load_or_create_config(config_path: PathBuf) -> AppResult<Config> {
                     &config_path.display()
                 );
                 println!("Attempting to create default config file.");
-                Config::make_default_config(&config_path)
+                let config = Config::make_default_config(&config_path)?;
+                println!("File created at {}.", config_path.display());
+                Ok(config)
             },
             _ => Err(e),
         },
@@ -44,272 +67,784 @@ fn main() -> AppResult<()> {
         Some(path) => PathBuf::from(path),
         None => Config::default_config_path(),
     };
-    let config = load_or_create_config(config_path)?;
+    let mut config = load_or_create_config(config_path)?;
+    if let Some(tz) = matches.value_of("tz") {
+        config.timezone = tz.into();
+    }
+    config.tz().map_err(|_| {
+        AppError::from_str(&format!("Unknown timezone {}.", config.timezone))
+    })?;
+    let output = Output::from_matches(&matches)?;
+
+    // With a daemon running, these commands go through it instead of opening
+    // the database.
+    if let Some(client) = Client::connect(&daemon::socket_path(&config)) {
+        let backend = &mut Backend::Daemon(client);
+        match matches.subcommand() {
+            ("log", Some(sub_matches)) => {
+                return log(backend, &config, output, sub_matches)
+            },
+            ("start", Some(sub_matches)) => {
+                return timer_start(backend, &config, output, sub_matches)
+            },
+            ("status", Some(sub_matches)) => {
+                return timer_status(backend, &config, output, sub_matches)
+            },
+            ("stop", Some(sub_matches)) => {
+                return timer_stop(backend, &config, output, sub_matches)
+            },
+            _ => {},
+        }
+    }
 
-    let mut conn = Connection::open(config.data_dir.join("faramir.db"))?;
-    db::init_db(&conn)?;
+    let db_path = config.data_dir.join("faramir.db");
+    let mut store = Store::open(&db_path)?;
+    for backup in store.backups() {
+        println!(
+            "Backed up database to {} before migrating.",
+            backup.display()
+        );
+    }
 
     match matches.subcommand() {
         ("add", Some(sub_matches)) => {
-            timer_add(&mut conn, &config, sub_matches)
+            timer_add(&mut store, &config, sub_matches)
+        },
+        ("cancel", Some(sub_matches)) => {
+            timer_cancel(&mut store, &config, sub_matches)
         },
+        ("check", Some(sub_matches)) => check(&store, &config, sub_matches),
+        ("daemon", Some(_)) => daemon::run(store, config),
         ("completions", Some(sub_matches)) => {
             completions(&mut app, &config, sub_matches)
         },
-        ("edit", Some(sub_matches)) => timer_edit(&conn, &config, sub_matches),
-        ("log", Some(sub_matches)) => log(&conn, sub_matches),
-        ("ls", Some(sub_matches)) => timer_ls(&conn, sub_matches),
-        ("rename", Some(sub_matches)) => rename(&conn, sub_matches),
-        ("rm", Some(sub_matches)) => rm(&mut conn, sub_matches),
-        ("start", Some(sub_matches)) => timer_start(&mut conn, sub_matches),
-        ("stats", Some(sub_matches)) => stats(&conn, sub_matches),
-        ("status", Some(sub_matches)) => {
-            timer_status(&conn, &config, sub_matches)
-        },
-        ("stop", Some(sub_matches)) => timer_stop(&conn, sub_matches),
+        ("edit", Some(sub_matches)) => edit(&mut store, &config, sub_matches),
+        ("export", Some(sub_matches)) => export(&store, &config, sub_matches),
+        ("import", Some(sub_matches)) => {
+            import(&mut store, &config, sub_matches)
+        },
+        ("log", Some(sub_matches)) => {
+            log(&mut Backend::Direct(&mut store), &config, output, sub_matches)
+        },
+        ("ls", Some(sub_matches)) => {
+            timer_ls(&store, &config, output, sub_matches)
+        },
+        ("merge", Some(sub_matches)) => merge(&mut store, sub_matches),
+        ("pause", Some(sub_matches)) => {
+            timer_pause(&store, &config, sub_matches)
+        },
+        ("rename", Some(sub_matches)) => rename(&store, sub_matches),
+        ("report", Some(sub_matches)) => {
+            report(&store, &config, sub_matches)
+        },
+        ("restart", Some(sub_matches)) => {
+            restart(&mut store, &config, output, sub_matches)
+        },
+        ("rm", Some(sub_matches)) => {
+            rm(&mut store, &config, output, sub_matches)
+        },
+        ("serve", Some(sub_matches)) => serve::serve(
+            &mut store,
+            &config,
+            sub_matches.value_of("bind").unwrap_or(serve::DEFAULT_BIND),
+        ),
+        ("resume", Some(sub_matches)) => {
+            timer_resume(&store, &config, sub_matches)
+        },
+        ("start", Some(sub_matches)) => timer_start(
+            &mut Backend::Direct(&mut store),
+            &config,
+            output,
+            sub_matches,
+        ),
+        ("stats", Some(_)) => stats(&store, output),
+        ("status", Some(sub_matches)) => timer_status(
+            &mut Backend::Direct(&mut store),
+            &config,
+            output,
+            sub_matches,
+        ),
+        ("stop", Some(sub_matches)) => timer_stop(
+            &mut Backend::Direct(&mut store),
+            &config,
+            output,
+            sub_matches,
+        ),
         ("", None) => Err(AppError::from_str("A subcommand must be provided.")),
         _ => Err(AppError::from_str("A subcommand must be provided.")),
     }
 }
 
-fn stats(conn: &Connection, sub_matches: &ArgMatches) -> AppResult<()> {
-    let projects = Projects::all(&conn)?;
+/// Asks the user to type 'y' before going through with `action`.
+fn confirm(question: &str, action: &str) -> AppResult<()> {
+    eprintln!("{}\nIf so, type 'y'.", question);
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    if input.trim_end() != "y" {
+        return Err(AppError::from_str(&format!(
+            "A confirmation with 'y' is needed to {}.",
+            action
+        )));
+    }
 
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct ProjectStats {
+    project:       String,
+    timers:        usize,
+    total_seconds: i64,
+}
+
+fn stats(store: &Store, output: Output) -> AppResult<()> {
+    let projects = store.projects()?;
+    let segments = store.segments_by_timer()?;
+
+    let mut stats = vec![];
     for project in projects.0 {
-        let timers = Timers::for_project(&conn, project.id)?;
+        let timers = store.project_timers(&project.name)?;
+        stats.push(ProjectStats {
+            timers:        timers.len(),
+            total_seconds: timers.total_seconds(&segments),
+            project:       project.name,
+        });
+    }
+
+    if !output.is_text() {
+        return output.list(&stats);
+    }
+
+    for stat in stats {
+        println!("Project {} - {} timer(s) found.", stat.project, stat.timers);
+        println!("total seconds: {}", stat.total_seconds);
+        println!("foramtted: {}", utils::format_seconds(stat.total_seconds));
+    }
+
+    Ok(())
+}
+
+fn report(
+    store: &Store, config: &Config, sub_matches: &ArgMatches,
+) -> AppResult<()> {
+    let mut filter = TimerFilter::from_matches(config, sub_matches)?;
+    filter.include_running = sub_matches.is_present("include-running");
+    let group_by = match sub_matches.value_of("group-by") {
+        Some(group_by) => group_by.parse::<GroupBy>()?,
+        None => GroupBy::Project,
+    };
+
+    print_report(&store.report(config, &filter, group_by)?);
+    Ok(())
+}
+
+fn print_report(report: &Report) {
+    if report.timer_count == 0 {
+        println!("No timers found.");
+        return;
+    }
+
+    println!("{} timer(s) found.", report.timer_count);
+    for group in &report.groups {
         println!(
-            "Project {} - {} timer(s) found.",
-            project.name,
-            timers.len()
+            "{} - {} ({})",
+            group.name,
+            utils::format_seconds(group.seconds),
+            percentage(group.seconds, report.total_seconds)
         );
-        let total_seconds = timers.total_seconds();
 
-        println!("total seconds: {}", total_seconds);
-        println!("foramtted: {}", utils::format_seconds(total_seconds));
+        for subgroup in &group.subgroups {
+            println!(
+                "  {} - {} ({})",
+                subgroup.name,
+                utils::format_seconds(subgroup.seconds),
+                percentage(subgroup.seconds, group.seconds)
+            );
+        }
     }
+    println!("Total: {}", utils::format_seconds(report.total_seconds));
+}
 
-    Ok(())
+fn percentage(part: i64, whole: i64) -> String {
+    if whole == 0 {
+        return "0.0%".into();
+    }
+
+    format!("{:.1}%", part as f64 * 100.0 / whole as f64)
 }
 
-fn rm(conn: &mut Connection, sub_matches: &ArgMatches) -> AppResult<()> {
+fn rm(
+    store: &mut Store, config: &Config, output: Output,
+    sub_matches: &ArgMatches,
+) -> AppResult<()> {
     let id = sub_matches.value_of("id").unwrap();
     let autoconfirm = sub_matches.is_present("yes");
 
-    match sub_matches.value_of("type").unwrap() {
-        "t" | "timer" | "timers" => db::delete_timer(&conn, id),
+    let removed = match sub_matches.value_of("type").unwrap() {
+        "t" | "timer" | "timers" => {
+            let timer = store.delete_timer(id)?;
+            if output.is_text() {
+                let end = match &timer.end {
+                    Some(end) => config.format_time(end, false),
+                    None => "running".into(),
+                };
+                println!(
+                    "Successfully deleted timer {} - start: {}, end: {}",
+                    timer.rid,
+                    config.format_time(&timer.start, false),
+                    end
+                );
+            }
+            Removed {
+                kind:   "timer",
+                name:   timer.rid,
+                timers: 1,
+            }
+        },
         "p" | "project" | "projects" => {
-            db::delete_project(conn, id, autoconfirm)
+            let count = store.project_timers(id)?.len();
+            if count > 0 && !autoconfirm {
+                confirm(
+                    &format!(
+                        "Project {} has {} timers associated with it. Are \
+                         you sure you want to remove it?",
+                        id, count
+                    ),
+                    "delete",
+                )?;
+            }
+
+            let timers = store.delete_project(id)?;
+            if output.is_text() {
+                println!("Successfully removed project {}.", id);
+            }
+            Removed {
+                kind: "project",
+                name: id.into(),
+                timers,
+            }
+        },
+        "ta" | "tag" | "tags" => {
+            let count = store.tag_timers(id)?.len();
+            if count > 0 && !autoconfirm {
+                confirm(
+                    &format!(
+                        "Tag {} has {} timers associated with it. Are you \
+                         sure you want to remove it?",
+                        id, count
+                    ),
+                    "delete",
+                )?;
+            }
+
+            let timers = store.delete_tag(id)?;
+            if output.is_text() {
+                println!("Succesfully removed tag {}.", id);
+            }
+            Removed {
+                kind: "tag",
+                name: id.into(),
+                timers,
+            }
         },
-        "ta" | "tag" | "tags" => db::delete_tag(&conn, id, autoconfirm),
         _ => {
             println!(
                 "Type not recognized. Run `faramir rename --help` for \
                  possible values."
             );
-            Err(AppError::from_str(
+            return Err(AppError::from_str(
                 "Type not recognized for `rename` subcommand.".into(),
-            ))
+            ));
         },
+    };
+
+    match output.is_text() {
+        true => Ok(()),
+        false => output.document(&removed),
     }
 }
 
 fn timer_add(
-    conn: &mut Connection, config: &Config, sub_matches: &ArgMatches,
+    store: &mut Store, config: &Config, sub_matches: &ArgMatches,
 ) -> AppResult<()> {
-    let tz: Tz = config.timezone.parse()?;
     let project = sub_matches.value_of("project").unwrap();
-    let tags = sub_matches.value_of("tags");
+    let tags =
+        utils::parse_tags(sub_matches.value_of("tags")).unwrap_or_default();
     let note = match sub_matches.value_of("note") {
         Some(note_str) => Some(note_str.into()),
         None => None,
     };
 
-    if let Some(start_str) = sub_matches.value_of("start") {
-        let end_str = match sub_matches.value_of("end") {
-            Some(end_str) => end_str,
-            None => {
-                println!(
-                    "If -s/--start is specified, -e/--end must also be \
-                     specified."
-                );
-                return Ok(());
+    let start = match sub_matches.value_of("start") {
+        Some(start_str) => Some(timeparse::parse_time(start_str, config)?),
+        None => None,
+    };
+    let end = match sub_matches.value_of("end") {
+        Some(end_str) => Some(timeparse::parse_time(end_str, config)?),
+        None => None,
+    };
+    let duration = match sub_matches.value_of("duration") {
+        Some(duration_str) => Some(timeparse::parse_duration(duration_str)?),
+        None => None,
+    };
+
+    let out_of_range = || {
+        AppError::from_str("The timer's --duration reaches too far in time.")
+    };
+    let (start_utc, end_utc) = match (start, end, duration) {
+        (Some(start), Some(end), None) => (start, end),
+        (Some(start), None, Some(duration)) => (
+            start,
+            start.checked_add_signed(duration).ok_or_else(out_of_range)?,
+        ),
+        (None, Some(end), Some(duration)) => (
+            end.checked_sub_signed(duration).ok_or_else(out_of_range)?,
+            end,
+        ),
+        (None, None, Some(duration)) => {
+            let now = Utc::now();
+            let start =
+                now.checked_sub_signed(duration).ok_or_else(out_of_range)?;
+            (start, now)
+        },
+        _ => {
+            return Err(AppError::from_str(
+                "Specify -s/--start and -e/--end, or -d/--duration with at \
+                 most one of them.",
+            ))
+        },
+    };
+
+    let mut create_timer = CreateTimer::new(start_utc, Some(end_utc), note);
+
+    if sub_matches.is_present("confirm") {
+        let edited = editor::edit_json(&config.data_dir, &create_timer)?;
+        create_timer = match edited {
+            Some(timer) => timer,
+            None => return Ok(()),
+        };
+    }
+
+    let timer = overlap::save(
+        store,
+        config,
+        create_timer.start,
+        create_timer.end,
+        &[],
+        sub_matches.is_present("allow_overlap"),
+        |store, start, end, allow_overlap| {
+            create_timer.start = start;
+            create_timer.end = end;
+            store.add(project, &tags, &create_timer, allow_overlap)
+        },
+    )?;
+    println!("Successfully added timer {}.", timer.rid);
+
+    Ok(())
+}
+
+fn timer_status(
+    backend: &mut Backend, config: &Config, output: Output,
+    sub_matches: &ArgMatches,
+) -> AppResult<()> {
+    if let Some(format) = sub_matches.value_of("format") {
+        let watch = match sub_matches.value_of("watch") {
+            Some(secs) => match secs.parse::<u64>() {
+                Ok(secs) if secs > 0 => Some(secs),
+                _ => {
+                    return Err(AppError::from_str(
+                        "--watch must be a positive number of seconds.",
+                    ))
+                },
             },
+            None => None,
         };
+        return status_bar(backend, config, format.parse()?, watch);
+    }
 
-        let start_dt = tz.datetime_from_str(&start_str, &config.time_format)?;
-        let start_utc = start_dt.with_timezone(&Utc);
+    let timers = backend.status()?;
 
-        let end_dt = tz.datetime_from_str(&end_str, &config.time_format)?;
-        let end_utc = end_dt.with_timezone(&Utc);
+    if !output.is_text() {
+        return output.list(&timers);
+    }
 
-        let mut create_timer = CreateTimer::new(start_utc, Some(end_utc), note);
+    if timers.is_empty() {
+        println!("No timers are running.");
+        return Ok(());
+    }
 
-        if sub_matches.is_present("confirm") {
-            let file_name =
-                format!(".faramir-edit-{}.tmp.json", utils::rand_string(5));
-            let tmp_file_path = &config.data_dir.join(file_name);
+    println!("{} timer(s) found.", timers.len());
+    let palette = backend.palette()?;
+    timer_table(&palette, config, &timers, sub_matches.is_present("detailed"))
+        .print(output.color())
+}
 
-            let editor = match env::var("EDITOR") {
-                Ok(editor) => editor,
-                Err(_) => {
-                    println!("Please set the EDITOR environment variable.");
-                    return Ok(());
+/// Prints the status for a bar, and again every `watch` seconds if set. The
+/// database, or the connection to the daemon, stays open between updates.
+fn status_bar(
+    backend: &mut Backend, config: &Config, format: BarFormat,
+    watch: Option<u64>,
+) -> AppResult<()> {
+    let mut stdout = io::stdout();
+    loop {
+        let timers = backend.status()?;
+        let status =
+            statusbar::render(format, config, &timers, watch.is_some());
+        writeln!(stdout, "{}", status)?;
+
+        match watch {
+            Some(secs) => thread::sleep(time::Duration::from_secs(secs)),
+            None => return Ok(()),
+        }
+    }
+}
+
+fn timer_start(
+    backend: &mut Backend, config: &Config, output: Output,
+    sub_matches: &ArgMatches,
+) -> AppResult<()> {
+    let mut params = start_params(
+        config,
+        sub_matches,
+        sub_matches.value_of("project").map(|p| p.into()),
+        utils::parse_tags(sub_matches.value_of("tags")).unwrap_or_default(),
+        sub_matches.value_of("note").map(|n| n.into()),
+    )?;
+    params.keep = sub_matches.is_present("keep");
+
+    start_timer(backend, config, output, &params)
+}
+
+fn restart(
+    store: &mut Store, config: &Config, output: Output,
+    sub_matches: &ArgMatches,
+) -> AppResult<()> {
+    let previous = match sub_matches.value_of("id") {
+        Some(rid) => Some(store.edit_timer(rid)?),
+        None => match sub_matches.value_of("project") {
+            Some(name) => {
+                let timer = store.last_project_timer(name)?;
+                Some(store.edit_timer(&timer.rid)?)
+            },
+            None => None,
+        },
+    };
+
+    let (project, tags, old_note) = match previous {
+        Some(previous) => {
+            (previous.project, previous.tags, previous.timer.note)
+        },
+        None => {
+            let count = match sub_matches.value_of("last") {
+                Some(count) => match count.parse::<usize>() {
+                    Ok(count) if count > 0 => count,
+                    _ => {
+                        return Err(AppError::from_str(
+                            "The count must be a positive number.",
+                        ))
+                    },
                 },
+                None => 10,
             };
+            let (project, tags) = pick_combination(store, count)?;
+            (project, tags, None)
+        },
+    };
 
-            let json = serde_json::to_string_pretty(&create_timer)?;
-            fs::write(&tmp_file_path, &json)?;
+    let note = match sub_matches.value_of("note") {
+        Some(note_str) => Some(note_str.into()),
+        None => match sub_matches.is_present("keep_note") {
+            true => old_note,
+            false => None,
+        },
+    };
 
-            Command::new(editor)
-                .arg(&tmp_file_path.to_str().unwrap())
-                .status()?;
+    let params = start_params(config, sub_matches, Some(project), tags, note)?;
+    start_timer(&mut Backend::Direct(store), config, output, &params)
+}
 
-            let content = fs::read_to_string(&tmp_file_path)?;
-            create_timer = match serde_json::from_str(&content) {
-                Ok(timer) => timer,
-                Err(e) => return Err(AppError::from(e)),
-            };
+/// Lists the `count` most recent project/tag combinations on stderr, so
+/// they stay out of `--output json`, and reads the user's pick from stdin.
+fn pick_combination(
+    store: &Store, count: usize,
+) -> AppResult<(String, Vec<String>)> {
+    let mut combinations = store.recent_combinations(count)?;
+    if combinations.is_empty() {
+        return Err(AppError::from_str("There are no timers to restart."));
+    }
+
+    for (i, (project, tags)) in combinations.iter().enumerate() {
+        match tags.is_empty() {
+            true => eprintln!("{}) {}", i + 1, project),
+            false => eprintln!("{}) {} [{}]", i + 1, project, tags.join(", ")),
         }
+    }
+    eprintln!("Which one should be restarted?");
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
 
-        db::handle_inserts(conn, project, tags, &create_timer)?;
-        println!("Successfully added timer {}.", create_timer.rid);
+    match input.trim().parse::<usize>() {
+        Ok(n) if n >= 1 && n <= combinations.len() => {
+            Ok(combinations.swap_remove(n - 1))
+        },
+        _ => Err(AppError::from_str(&format!(
+            "Please pick a number from 1 to {}.",
+            combinations.len()
+        ))),
     }
+}
 
-    Ok(())
+/// The params for starting a timer now, or at `--at`. Running timers are
+/// stopped first with `--switch` or `single_active`.
+fn start_params(
+    config: &Config, sub_matches: &ArgMatches, project: Option<String>,
+    tags: Vec<String>, note: Option<String>,
+) -> AppResult<StartParams> {
+    Ok(StartParams {
+        project,
+        tags,
+        note,
+        at: sub_matches
+            .value_of("at")
+            .map(|at| timeparse::parse_time(at, config))
+            .transpose()?,
+        keep: false,
+        switch: config.single_active || sub_matches.is_present("switch"),
+        allow_overlap: sub_matches.is_present("allow_overlap"),
+    })
 }
 
-fn timer_status(
-    conn: &Connection, config: &Config, sub_matches: &ArgMatches,
+fn start_timer(
+    backend: &mut Backend, config: &Config, output: Output,
+    params: &StartParams,
 ) -> AppResult<()> {
-    let timers = Timers::currently_running(&conn)?;
+    let started = backend.start(config, params)?;
 
-    if timers.len() == 0 {
-        println!("No timers are running.");
-        return Ok(());
+    if !output.is_text() {
+        return output.document(&started);
     }
 
-    println!("{} timer(s) found.", timers.len());
-    for timer in timers.0 {
-        let project = Project::for_timer(&conn, timer.id)?;
-        println!("timer for project {} - with id {}", project.name, timer.rid);
-        timer.pretty_print(&config, sub_matches.is_present("detailed"));
+    for timer in &started.stopped {
+        println!("Stopped timer {}.", timer.timer.rid);
     }
+    println!(
+        "Successfully started timer {} for project {}.",
+        started.started.timer.rid,
+        started.started.project.unwrap_or_default()
+    );
 
     Ok(())
 }
 
-fn timer_start(
-    conn: &mut Connection, sub_matches: &ArgMatches,
+fn timer_cancel(
+    store: &mut Store, config: &Config, sub_matches: &ArgMatches,
 ) -> AppResult<()> {
-    let note = match sub_matches.value_of("note") {
-        Some(note_str) => Some(note_str.into()),
-        None => None,
+    let mut current_timers = store.running()?;
+
+    if let Some(rid) = sub_matches.value_of("id") {
+        current_timers.0.retain(|t| t.rid == rid);
+    }
+
+    let timer = match current_timers.0.into_iter().max_by_key(|t| t.start) {
+        Some(timer) => timer,
+        None => {
+            match sub_matches.value_of("id") {
+                Some(_) => println!("No currently running timer has that id."),
+                None => println!("No timers are running."),
+            }
+            return Ok(());
+        },
     };
 
-    match sub_matches.is_present("keep") {
-        true => {
-            let last_timer = Timer::last(&conn)?;
-            let project = Project::for_timer(&conn, last_timer.id)?;
-            let tags = Tags::for_timer(&conn, last_timer.id)?;
-            let tag_str = tags
-                .0
-                .into_iter()
-                .map(|t| t.name)
-                .collect::<Vec<String>>()
-                .join(",");
-            let mut create_timer = CreateTimer::default();
-            create_timer.note = note;
-
-            db::handle_inserts(
-                conn,
-                &project.name,
-                Some(&tag_str),
-                &create_timer,
-            )?;
-        },
-        false => {
-            let project = match sub_matches.value_of("project") {
-                Some(p) => p,
-                None => {
-                    return Err(AppError::from_str(
-                        "Please specify a project name.",
-                    ));
-                },
-            };
-            let tag_str = sub_matches.value_of("tags");
+    let project = store.timer_project(&timer)?;
+    store.delete_timer(&timer.rid)?;
 
-            let mut create_timer = CreateTimer::default();
-            create_timer.note = note;
+    println!(
+        "Cancelled timer {} for project {}, started at {}.",
+        timer.rid,
+        project.name,
+        config.format_time(&timer.start, false)
+    );
+    Ok(())
+}
 
-            db::handle_inserts(conn, project, tag_str, &create_timer)?;
-            println!(
-                "Successfully started timer {} for project {}.",
-                create_timer.rid, project
-            );
-        },
+fn timer_stop(
+    backend: &mut Backend, config: &Config, output: Output,
+    sub_matches: &ArgMatches,
+) -> AppResult<()> {
+    let params = StopParams {
+        id:  sub_matches.value_of("id").map(|id| id.into()),
+        all: sub_matches.is_present("all"),
+        at:  sub_matches
+            .value_of("at")
+            .map(|at| timeparse::parse_time(at, config))
+            .transpose()?,
     };
 
+    let stopped = match backend.stop(&params)? {
+        Stopped::Timers(timers) => timers,
+        Stopped::Nothing(reason) => return output.notice(&reason),
+    };
+
+    if !output.is_text() {
+        return output.list(&stopped);
+    }
+
+    if stopped.is_empty() {
+        println!("No timers are running.");
+    }
+    for timer in stopped {
+        println!("Stopped timer {}.", timer.timer.rid);
+    }
+
     Ok(())
 }
 
-fn timer_stop(conn: &Connection, sub_matches: &ArgMatches) -> AppResult<()> {
-    let mut current_timers = Timers::currently_running(&conn)?;
+fn timer_pause(
+    store: &Store, config: &Config, sub_matches: &ArgMatches,
+) -> AppResult<()> {
+    let at = match sub_matches.value_of("at") {
+        Some(at) => timeparse::parse_time(at, config)?,
+        None => Utc::now(),
+    };
 
-    match current_timers.len() {
-        0 => {
-            println!("No timers are running.");
+    let mut running = vec![];
+    for timer in store.running()?.0 {
+        if store.paused_since(&timer)?.is_none() {
+            running.push(timer);
+        }
+    }
+
+    match select_timer(running, sub_matches.value_of("id"), "running") {
+        Some(timer) => {
+            store.pause(&timer, at)?;
+            println!("Paused timer {}.", timer.rid);
             Ok(())
         },
-        1 => current_timers.0[0].stop(&conn),
-        _ => {
-            if sub_matches.is_present("all") {
-                return current_timers.stop_all(&conn);
-            }
+        None => Ok(()),
+    }
+}
 
-            match sub_matches.value_of("id") {
-                Some(rid) => {
-                    current_timers.0.retain(|t| t.rid == rid);
-                    match current_timers.0.first_mut() {
-                        Some(timer) => timer.stop(&conn),
-                        None => {
-                            println!("No currently running timer has that id.");
-                            Ok(())
-                        },
-                    }
-                },
-                None => {
-                    println!(
-                        "Multiple timers are running. Specify a timer with -i \
-                         <id>."
-                    );
-                    Ok(())
-                },
-            }
+fn timer_resume(
+    store: &Store, config: &Config, sub_matches: &ArgMatches,
+) -> AppResult<()> {
+    let at = match sub_matches.value_of("at") {
+        Some(at) => timeparse::parse_time(at, config)?,
+        None => Utc::now(),
+    };
+
+    let mut paused = vec![];
+    for timer in store.running()?.0 {
+        if store.paused_since(&timer)?.is_some() {
+            paused.push(timer);
+        }
+    }
+
+    match select_timer(paused, sub_matches.value_of("id"), "paused") {
+        Some(timer) => {
+            store.resume(&timer, at)?;
+            println!("Resumed timer {}.", timer.rid);
+            Ok(())
         },
+        None => Ok(()),
     }
 }
 
-fn ls_projects(conn: &Connection, sub_matches: &ArgMatches) -> AppResult<()> {
-    let projects = Projects::all(&conn)?;
-    if projects.len() == 0 {
+/// Picks the timer with `rid`, or the only one in `timers`. `state` describes
+/// the timers in messages, e.g. "paused".
+fn select_timer(
+    timers: Vec<Timer>, rid: Option<&str>, state: &str,
+) -> Option<Timer> {
+    if let Some(rid) = rid {
+        let timer = timers.into_iter().find(|t| t.rid == rid);
+        if timer.is_none() {
+            println!("No {} timer has that id.", state);
+        }
+        return timer;
+    }
+
+    match timers.len() {
+        0 => println!("No timers are {}.", state),
+        1 => return timers.into_iter().next(),
+        _ => println!(
+            "Multiple timers are {}. Specify a timer with -i <id>.",
+            state
+        ),
+    }
+
+    None
+}
+
+fn ls_projects(
+    store: &Store, output: Output, sub_matches: &ArgMatches,
+) -> AppResult<()> {
+    let projects = store.projects()?;
+    if !output.is_text() {
+        return output.list(&projects.0);
+    }
+
+    if projects.is_empty() {
         println!("No projects found.");
         println!("Projects are automatically created when you start a timer:");
         println!("  faramir start project1 -t tag1,tag2");
         return Ok(());
     }
 
-    match sub_matches.is_present("detailed") {
-        true => projects.print_detailed(&conn)?,
-        false => projects.print_basic(),
+    println!("{} Project(s) found.", projects.len());
+    project_table(store, projects, sub_matches.is_present("detailed"))?
+        .print(output.color())
+}
+
+/// Lays out projects with their descriptions, or with their timer counts and
+/// total time when `detailed`.
+fn project_table(
+    store: &Store, projects: Projects, detailed: bool,
+) -> AppResult<Table> {
+    let mut table = match detailed {
+        true => Table::new(vec!["NAME", "TIMERS", "TOTAL"]),
+        false => Table::new(vec!["NAME", "DESCRIPTION"]),
+    };
+    let segments = match detailed {
+        true => store.segments_by_timer()?,
+        false => HashMap::new(),
+    };
+
+    for project in projects.0 {
+        let cells = match detailed {
+            true => {
+                let timers = store.project_timers(&project.name)?;
+                vec![
+                    project_cell(&project),
+                    Cell::plain(&timers.len().to_string()),
+                    Cell::plain(&utils::format_seconds(
+                        timers.total_seconds(&segments),
+                    )),
+                ]
+            },
+            false => vec![
+                project_cell(&project),
+                Cell::plain(project.description.as_deref().unwrap_or("")),
+            ],
+        };
+        table.push(cells);
     }
 
-    Ok(())
+    Ok(table)
 }
 
-fn ls_tags(conn: &Connection, _sub_matches: &ArgMatches) -> AppResult<()> {
+fn ls_tags(
+    store: &Store, output: Output, _sub_matches: &ArgMatches,
+) -> AppResult<()> {
     //TODO detailed
-    let tags = Tags::all(&conn)?;
-    if tags.len() == 0 {
+    let tags = store.tags()?;
+    if !output.is_text() {
+        return output.list(&tags.0);
+    }
+
+    if tags.is_empty() {
         println!("No projects found.");
         println!("Tags are automatically created when you start a timer:");
         println!("  faramir start project1 -t tag1,tag2");
@@ -317,16 +852,109 @@ fn ls_tags(conn: &Connection, _sub_matches: &ArgMatches) -> AppResult<()> {
     }
 
     println!("{} Tag(s) found.", tags.len());
-    println!("tags: {}", tags.names().join(", "));
-    Ok(())
+    let mut table = Table::new(vec!["NAME", "DESCRIPTION"]);
+    for tag in tags.0 {
+        table.push(vec![
+            Cell::colored(
+                &tag.name,
+                name_color(&tag.name, tag.color.as_deref()),
+            ),
+            Cell::plain(tag.description.as_deref().unwrap_or("")),
+        ]);
+    }
+    table.print(output.color())
+}
+
+fn ls_timers(
+    store: &Store, config: &Config, output: Output, sub_matches: &ArgMatches,
+) -> AppResult<()> {
+    let mut timers = match (
+        sub_matches.is_present("finished"),
+        sub_matches.is_present("running"),
+    ) {
+        (true, false) => store.timers(&TimerFilter::default())?,
+        (false, true) => store.running()?,
+        _ => store.timers(&TimerFilter {
+            include_running: true,
+            ..Default::default()
+        })?,
+    };
+    timers.0.sort_by_key(|t| Reverse(t.start));
+    let timers = TimerOutput::load_all(store, timers)?;
+
+    if !output.is_text() {
+        return output.list(&timers);
+    }
+
+    if timers.is_empty() {
+        println!("No timers found.");
+        return Ok(());
+    }
+
+    println!("{} timer(s) found.", timers.len());
+    let palette = Palette::load(store)?;
+    timer_table(&palette, config, &timers, sub_matches.is_present("detailed"))
+        .print(output.color())
+}
+
+/// Lays out timers as a table, with running timers highlighted. Detailed
+/// tables use `full_time_format`.
+fn timer_table(
+    palette: &Palette, config: &Config, timers: &[TimerOutput], detailed: bool,
+) -> Table {
+    let mut table = Table::new(vec![
+        "ID", "PROJECT", "TAGS", "START", "END", "DURATION", "NOTE",
+    ]);
+    for output in timers {
+        let timer = &output.timer;
+        let project = match &output.project {
+            Some(name) => palette.project(name),
+            None => Cell::plain("(no project)"),
+        };
+        let end = match (timer.end, output.paused_since) {
+            (Some(end), _) => {
+                Cell::plain(&config.format_time(&end, detailed))
+            },
+            (None, Some(paused)) => Cell::colored(
+                &format!(
+                    "paused at {}",
+                    config.format_time(&paused, detailed)
+                ),
+                Color::Yellow,
+            ),
+            (None, None) => Cell::colored("running", Color::Green),
+        };
+
+        let cells = vec![
+            Cell::plain(&timer.rid),
+            project,
+            palette.tags(&output.tags),
+            Cell::plain(&config.format_time(&timer.start, detailed)),
+            end,
+            Cell::plain(&utils::format_seconds(output.duration)),
+            Cell::plain(timer.note.as_deref().unwrap_or("")),
+        ];
+        match timer.end {
+            Some(_) => table.push(cells),
+            None => table.push_highlighted(cells),
+        }
+    }
+
+    table
 }
 
 // type.unwrap() is fine because clap handles it if it's not provided
-fn timer_ls(conn: &Connection, sub_matches: &ArgMatches) -> AppResult<()> {
+fn timer_ls(
+    store: &Store, config: &Config, output: Output, sub_matches: &ArgMatches,
+) -> AppResult<()> {
     match sub_matches.value_of("type").unwrap() {
-        "p" | "project" | "projects" => ls_projects(&conn, &sub_matches),
-        "ta" | "tag" | "tags" => ls_tags(&conn, &sub_matches),
-        "t" | "timer" | "timers" => Ok(()),
+        "p" | "project" | "projects" => {
+            ls_projects(store, output, sub_matches)
+        },
+        "ta" | "tag" | "tags" => ls_tags(store, output, sub_matches),
+        "t" | "timer" | "timers" => {
+            ls_timers(store, config, output, sub_matches)
+        },
         _ => {
             println!(
                 "Type not recognized. Run `faramir ls --help` for possible \
@@ -339,11 +967,71 @@ fn timer_ls(conn: &Connection, sub_matches: &ArgMatches) -> AppResult<()> {
     }
 }
 
+fn merge(store: &mut Store, sub_matches: &ArgMatches) -> AppResult<()> {
+    let from = sub_matches.value_of("from").unwrap();
+    let into = sub_matches.value_of("into").unwrap();
+    let autoconfirm = sub_matches.is_present("yes");
+
+    match sub_matches.value_of("type").unwrap() {
+        "p" | "project" | "projects" => {
+            let count = store.project_timers(from)?.len();
+            store.project(into)?;
+            if !autoconfirm {
+                confirm(
+                    &format!(
+                        "Move {} timer(s) from project {} to {} and remove \
+                         {}?",
+                        count, from, into, from
+                    ),
+                    "merge",
+                )?;
+            }
+
+            let moved = store.merge_projects(from, into)?;
+            println!(
+                "Successfully merged project {} into {} ({} timer(s) moved).",
+                from, into, moved
+            );
+            Ok(())
+        },
+        "ta" | "tag" | "tags" => {
+            let count = store.tag_timers(from)?.len();
+            store.tag(into)?;
+            if !autoconfirm {
+                confirm(
+                    &format!(
+                        "Move {} timer(s) from tag {} to {} and remove {}?",
+                        count, from, into, from
+                    ),
+                    "merge",
+                )?;
+            }
+
+            let moved = store.merge_tags(from, into)?;
+            println!(
+                "Successfully merged tag {} into {} ({} timer(s) moved).",
+                from, into, moved
+            );
+            Ok(())
+        },
+        _ => {
+            println!(
+                "Type not recognized. Run `faramir merge --help` for \
+                 possible values."
+            );
+            Err(AppError::from_str(
+                "Type not recognized for `merge` subcommand.",
+            ))
+        },
+    }
+}
+
 fn rename_project(
-    conn: &Connection, old_name: &str, new_name: &str,
+    store: &Store, old_name: &str, new_name: &str,
 ) -> AppResult<()> {
-    if let Ok(project) = Project::find_by_name(&conn, &old_name) {
-        project.update(&conn, new_name)?;
+    if let Ok(mut project) = store.project(old_name) {
+        project.name = new_name.into();
+        store.update_project(&project)?;
         println!(
             "Successfully renamed project {} to {}.",
             &old_name, &new_name
@@ -356,10 +1044,11 @@ fn rename_project(
 }
 
 fn rename_tag(
-    conn: &Connection, old_name: &str, new_name: &str,
+    store: &Store, old_name: &str, new_name: &str,
 ) -> AppResult<()> {
-    if let Ok(tag) = Tag::find_by_name(&conn, &old_name) {
-        tag.update(&conn, new_name)?;
+    if let Ok(mut tag) = store.tag(old_name) {
+        tag.name = new_name.into();
+        store.update_tag(&tag)?;
         println!("Successfully renamed tag {} to {}.", &old_name, &new_name);
     } else {
         println!("Unable to find tag with name {}.", &old_name);
@@ -368,14 +1057,14 @@ fn rename_tag(
     Ok(())
 }
 
-fn rename(conn: &Connection, sub_matches: &ArgMatches) -> AppResult<()> {
+fn rename(store: &Store, sub_matches: &ArgMatches) -> AppResult<()> {
     let old_name = sub_matches.value_of("old-name").unwrap();
     let new_name = sub_matches.value_of("new-name").unwrap();
     match sub_matches.value_of("type").unwrap() {
         "p" | "project" | "projects" => {
-            rename_project(&conn, old_name, new_name)
+            rename_project(store, old_name, new_name)
         },
-        "ta" | "tag" | "tags" => rename_tag(&conn, old_name, new_name),
+        "ta" | "tag" | "tags" => rename_tag(store, old_name, new_name),
         _ => {
             println!(
                 "Type not recognized. Run `faramir rename --help` for \
@@ -388,27 +1077,122 @@ fn rename(conn: &Connection, sub_matches: &ArgMatches) -> AppResult<()> {
     }
 }
 
-fn log(conn: &Connection, sub_matches: &ArgMatches) -> AppResult<()> {
-    let limit = match sub_matches.value_of("limit") {
-        Some(l) => l,
-        None => "10",
+fn export(
+    store: &Store, config: &Config, sub_matches: &ArgMatches,
+) -> AppResult<()> {
+    let mut filter = TimerFilter::from_matches(config, sub_matches)?;
+    filter.include_running = true;
+    let format = match sub_matches.value_of("format") {
+        Some(format) => format.parse::<export::Format>()?,
+        None => export::Format::Json,
+    };
+    let tz: Option<Tz> = match sub_matches.is_present("local") {
+        true => Some(config.tz()?),
+        false => None,
     };
 
-    let timers = Timers::limit(&conn, limit)?;
+    let timers = store.export(&filter, tz.as_ref())?;
 
-    println!("{} timer(s) retrieved.", timers.len());
-    for timer in timers.0 {
-        println!(
-            "{} - start: {}, end: {}",
-            timer.rid,
-            timer.start,
-            timer.end.unwrap()
-        );
+    match sub_matches.value_of("out") {
+        Some(path) => {
+            let mut f = BufWriter::new(File::create(path)?);
+            export::write(&mut f, format, &timers)?;
+            println!("Exported {} timer(s) to {}.", timers.len(), path);
+        },
+        None => export::write(&mut io::stdout(), format, &timers)?,
     }
 
     Ok(())
 }
 
+fn import(
+    store: &mut Store, config: &Config, sub_matches: &ArgMatches,
+) -> AppResult<()> {
+    let file = sub_matches.value_of("file").map(PathBuf::from);
+    let dry_run = sub_matches.is_present("dry-run");
+
+    let timers = match sub_matches.value_of("from") {
+        Some("watson") => {
+            watson::read(&file.unwrap_or_else(watson::default_dir))?
+        },
+        _ => {
+            let path = match file {
+                Some(path) => path,
+                None => {
+                    return Err(AppError::from_str(
+                        "Please specify a file to import.",
+                    ))
+                },
+            };
+            let format = match sub_matches.value_of("format") {
+                Some(format) => format.parse::<export::Format>()?,
+                None => import::detect_format(&path),
+            };
+            import::read(&path, format)?
+        },
+    };
+
+    let summary = store
+        .import(
+            timers,
+            sub_matches.is_present("update"),
+            dry_run,
+            sub_matches.is_present("allow_overlap"),
+        )
+        .map_err(|err| overlap::explain(config, err))?;
+
+    for rid in &summary.without_project {
+        println!("Skipping timer {}: it has no project.", rid);
+    }
+    for rid in &summary.lost_pauses {
+        eprintln!(
+            "Warning: timer {}'s duration doesn't match its start and end, \
+             but the file has no segments to restore its pauses from.",
+            rid
+        );
+    }
+    match dry_run {
+        true => println!(
+            "Dry run: {} timer(s) would be inserted, {} updated, {} skipped.",
+            summary.inserted, summary.updated, summary.skipped
+        ),
+        false => println!(
+            "Imported timers: {} inserted, {} updated, {} skipped.",
+            summary.inserted, summary.updated, summary.skipped
+        ),
+    }
+    Ok(())
+}
+
+fn log(
+    backend: &mut Backend, config: &Config, output: Output,
+    sub_matches: &ArgMatches,
+) -> AppResult<()> {
+    let mut filter = TimerFilter::from_matches(config, sub_matches)?;
+    filter.include_running = sub_matches.is_present("include-running");
+    filter.limit = match sub_matches.value_of("limit") {
+        Some(l) => match l.parse::<i64>() {
+            Ok(limit) if limit > 0 => Some(limit),
+            _ => {
+                return Err(AppError::from_str(
+                    "-l/--limit must be a positive number.",
+                ))
+            },
+        },
+        None if filter.has_range() => None,
+        None => Some(10),
+    };
+
+    let timers = backend.log(&filter)?;
+    if !output.is_text() {
+        return output.list(&timers);
+    }
+
+    println!("{} timer(s) retrieved.", timers.len());
+    let palette = backend.palette()?;
+    timer_table(&palette, config, &timers, false).print(output.color())
+}
+
 fn completions(
     app: &mut App, config: &Config, sub_matches: &ArgMatches,
 ) -> AppResult<()> {
@@ -434,51 +1218,155 @@ fn completions(
     Ok(())
 }
 
-fn timer_edit(
-    conn: &Connection, config: &Config, sub_matches: &ArgMatches,
+fn edit(
+    store: &mut Store, config: &Config, sub_matches: &ArgMatches,
 ) -> AppResult<()> {
-    let rid = sub_matches.value_of("id").unwrap();
-    let file_name = format!(".faramir-edit-{}.tmp.json", utils::rand_string(5));
-    let tmp_file_path = config.data_dir.join(file_name);
+    let id = sub_matches.value_of("id").unwrap();
+    let allow_overlap = sub_matches.is_present("allow_overlap");
+    match (id, sub_matches.value_of("name")) {
+        (_, None) => timer_edit(store, config, id, allow_overlap),
+        ("t" | "timer" | "timers", Some(rid)) => {
+            timer_edit(store, config, rid, allow_overlap)
+        },
+        ("p" | "project" | "projects", Some(name)) => {
+            project_edit(store, config, name)
+        },
+        ("ta" | "tag" | "tags", Some(name)) => tag_edit(store, config, name),
+        _ => {
+            println!(
+                "Type not recognized. Run `faramir edit --help` for possible \
+                 values."
+            );
+            Err(AppError::from_str(
+                "Type not recognized for `edit` subcommand.",
+            ))
+        },
+    }
+}
 
-    let old_timer = match Timer::find_by(&conn, "rid", rid) {
-        Ok(timer) => timer,
-        Err(e) => return Err(e),
+fn project_edit(store: &Store, config: &Config, name: &str) -> AppResult<()> {
+    let old_project = store.project(name)?;
+    let new_project = match editor::edit_json(&config.data_dir, &old_project)?
+    {
+        Some(project) => project,
+        None => return Ok(()),
     };
 
-    let json = serde_json::to_string_pretty(&old_timer)?;
-    fs::write(&tmp_file_path, &json)?;
+    if old_project.id != new_project.id {
+        println!("The IDs of the projects don't match.");
+        return Ok(());
+    }
 
-    let editor = match env::var("EDITOR") {
-        Ok(editor) => editor,
-        Err(_) => {
-            println!("Please set the EDITOR environment variable.");
-            return Ok(());
-        },
+    if new_project.name.is_empty() {
+        println!("A project must have a name.");
+        return Ok(());
+    }
+
+    if new_project.name != old_project.name
+        && store.project(&new_project.name).is_ok()
+    {
+        println!("A project named {} already exists.", new_project.name);
+        return Ok(());
+    }
+
+    store.update_project(&new_project)?;
+    println!("Updated project {}", new_project.name);
+    Ok(())
+}
+
+fn tag_edit(store: &Store, config: &Config, name: &str) -> AppResult<()> {
+    let old_tag = store.tag(name)?;
+    let new_tag = match editor::edit_json(&config.data_dir, &old_tag)? {
+        Some(tag) => tag,
+        None => return Ok(()),
     };
 
-    Command::new(editor)
-        .arg(&tmp_file_path.to_str().unwrap())
-        .status()?;
+    if old_tag.id != new_tag.id {
+        println!("The IDs of the tags don't match.");
+        return Ok(());
+    }
+
+    if new_tag.name.is_empty() || new_tag.name.contains(',') {
+        println!("A tag name must be non-empty and can't contain commas.");
+        return Ok(());
+    }
+
+    if new_tag.name != old_tag.name
+        && store.tag(&new_tag.name).is_ok()
+    {
+        println!("A tag named {} already exists.", new_tag.name);
+        return Ok(());
+    }
+
+    store.update_tag(&new_tag)?;
+    println!("Updated tag {}", new_tag.name);
+    Ok(())
+}
+
+fn check(
+    store: &Store, config: &Config, sub_matches: &ArgMatches,
+) -> AppResult<()> {
+    match sub_matches.value_of("what") {
+        Some("overlaps") => {
+            let pairs = store.overlap_pairs()?;
+
+            println!("{} overlapping pair(s) found.", pairs.len());
+            for (a, b) in pairs {
+                println!(
+                    "{} overlaps {}",
+                    overlap::describe(&a, config),
+                    overlap::describe(&b, config)
+                );
+            }
+            Ok(())
+        },
+        _ => Err(AppError::from_str("Nothing to check.")),
+    }
+}
 
-    let content = fs::read_to_string(&tmp_file_path)?;
-    let new_timer: Timer = match serde_json::from_str(&content) {
-        Ok(timer) => timer,
-        Err(e) => return Err(AppError::from(e)),
+fn timer_edit(
+    store: &mut Store, config: &Config, rid: &str, allow_overlap: bool,
+) -> AppResult<()> {
+    let old_timer = store.edit_timer(rid)?;
+    let mut new_timer = match editor::edit_json(&config.data_dir, &old_timer)?
+    {
+        Some(timer) => timer,
+        None => return Ok(()),
     };
 
-    if old_timer.id != new_timer.id {
+    if old_timer.timer.id != new_timer.timer.id {
         println!("The IDs of the timers don't match.");
         return Ok(());
     }
 
-    if old_timer.rid != new_timer.rid {
+    if old_timer.timer.rid != new_timer.timer.rid {
         println!("The RIDs of the timers don't match.");
         return Ok(());
     }
 
-    new_timer.update(&conn)?;
-    println!("Updated timer {}", new_timer.rid);
-    fs::remove_file(tmp_file_path)?;
+    if new_timer.project.is_empty() {
+        println!("A timer must have a project.");
+        return Ok(());
+    }
+
+    overlap::save(
+        store,
+        config,
+        new_timer.timer.start,
+        new_timer.timer.end,
+        &[new_timer.timer.id],
+        allow_overlap,
+        |store, start, end, allow_overlap| {
+            new_timer.timer.start = start;
+            new_timer.timer.end = end;
+            store.update_timer(
+                &new_timer.timer,
+                &new_timer.project,
+                &new_timer.tags,
+                allow_overlap,
+            )
+        },
+    )?;
+    println!("Updated timer {}", new_timer.timer.rid);
     Ok(())
 }
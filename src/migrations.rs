@@ -0,0 +1,307 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use rusqlite::Connection;
+
+use crate::errors::{AppError, AppResult};
+
+pub struct Migration {
+    pub version:     i32,
+    pub description: &'static str,
+    /// Destructive migrations drop or rewrite existing data, so the database
+    /// file gets backed up before they run.
+    pub destructive: bool,
+    pub sql:         &'static str,
+}
+
+/// Every schema change, in order. Never edit or reorder a migration that has
+/// been released; append a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version:     1,
+        description: "initial schema",
+        destructive: false,
+        sql:         "
+        CREATE TABLE IF NOT EXISTS projects (
+            id INTEGER PRIMARY KEY,
+            name TEXT UNIQUE NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS tags (
+            id INTEGER PRIMARY KEY,
+            name TEXT UNIQUE NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS timers (
+            id INTEGER PRIMARY KEY,
+            rid TEXT NOT NULL,
+            start TEXT NOT NULL,
+            end TEXT,
+            note TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS tags_timers (
+            tag_id INTEGER NOT NULL,
+            timer_id INTEGER NOT NULL,
+            FOREIGN KEY(tag_id) REFERENCES tags(id),
+            FOREIGN KEY(timer_id) REFERENCES timers(id)
+        );
+
+        CREATE TABLE IF NOT EXISTS projects_timers (
+            project_id INTEGER NOT NULL,
+            timer_id INTEGER NOT NULL,
+            FOREIGN KEY(project_id) REFERENCES projects(id),
+            FOREIGN KEY(timer_id) REFERENCES timers(id)
+        );
+
+        CREATE UNIQUE INDEX IF NOT EXISTS projects_timers_idx
+        ON projects_timers (project_id, timer_id);
+        ",
+    },
+    Migration {
+        version:     2,
+        description: "unique timer rids",
+        destructive: false,
+        sql:         "
+        CREATE UNIQUE INDEX IF NOT EXISTS timers_rid_idx ON timers (rid);
+        ",
+    },
+    Migration {
+        version:     3,
+        description: "project and tag metadata",
+        destructive: false,
+        sql:         "
+        ALTER TABLE projects ADD COLUMN description TEXT;
+        ALTER TABLE projects ADD COLUMN color TEXT;
+        ALTER TABLE projects ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE projects ADD COLUMN hourly_rate REAL;
+
+        ALTER TABLE tags ADD COLUMN description TEXT;
+        ALTER TABLE tags ADD COLUMN color TEXT;
+        ",
+    },
+    Migration {
+        version:     4,
+        description: "timer segments",
+        destructive: false,
+        sql:         "
+        CREATE TABLE timer_segments (
+            id INTEGER PRIMARY KEY,
+            timer_id INTEGER NOT NULL,
+            start TEXT NOT NULL,
+            end TEXT,
+            FOREIGN KEY(timer_id) REFERENCES timers(id)
+        );
+
+        CREATE INDEX timer_segments_timer_idx ON timer_segments (timer_id);
+
+        INSERT INTO timer_segments (timer_id, start, end)
+        SELECT id, start, end FROM timers;
+        ",
+    },
+];
+
+pub fn latest_version(migrations: &[Migration]) -> i32 {
+    migrations.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn current_version(conn: &Connection) -> AppResult<i32> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(AppError::from)
+}
+
+/// Brings the database up to the latest version, applying each pending
+/// migration in its own transaction. Returns the backups made along the way.
+pub fn migrate(
+    conn: &mut Connection, db_path: &Path,
+) -> AppResult<Vec<PathBuf>> {
+    apply(conn, db_path, MIGRATIONS)
+}
+
+/// `migrate` with the given `migrations` instead of `MIGRATIONS`.
+fn apply(
+    conn: &mut Connection, db_path: &Path, migrations: &[Migration],
+) -> AppResult<Vec<PathBuf>> {
+    let current = current_version(conn)?;
+    let latest = latest_version(migrations);
+
+    if current > latest {
+        return Err(AppError::from_str(&format!(
+            "The database at {} is at schema version {}, but this version of \
+             faramir only understands up to version {}. Please upgrade \
+             faramir.",
+            db_path.display(),
+            current,
+            latest
+        )));
+    }
+
+    let mut backups = vec![];
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        if migration.destructive {
+            backups.push(backup(db_path, migration.version - 1)?);
+        }
+
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql).map_err(|e| {
+            AppError::from_str(&format!(
+                "Migration {} ({}) failed: {}",
+                migration.version, migration.description, e
+            ))
+        })?;
+        tx.pragma_update(None, "user_version", &migration.version)?;
+        tx.commit()?;
+    }
+
+    Ok(backups)
+}
+
+fn backup(db_path: &Path, version: i32) -> AppResult<PathBuf> {
+    let file_name = format!(
+        "faramir.db.v{}-{}.bak",
+        version,
+        Utc::now().format("%Y%m%d%H%M%S")
+    );
+    let backup_path = match db_path.parent() {
+        Some(parent) => parent.join(file_name),
+        None => PathBuf::from(file_name),
+    };
+
+    fs::copy(db_path, &backup_path)?;
+    Ok(backup_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use rusqlite::NO_PARAMS;
+
+    use super::*;
+
+    fn tables(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table'")
+            .unwrap();
+        let names = stmt.query_map(NO_PARAMS, |row| row.get(0)).unwrap();
+        names.map(|name| name.unwrap()).collect()
+    }
+
+    fn count(conn: &Connection, table: &str) -> i64 {
+        let sql = format!("SELECT COUNT(*) FROM {}", table);
+        conn.query_row(&sql, NO_PARAMS, |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn fresh_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let backups = migrate(&mut conn, Path::new(":memory:")).unwrap();
+
+        assert!(backups.is_empty());
+        assert_eq!(current_version(&conn).unwrap(), latest_version(MIGRATIONS));
+        for table in &["projects", "tags", "timers", "timer_segments"] {
+            assert!(tables(&conn).contains(&table.to_string()));
+        }
+
+        // Running it again is a no-op.
+        migrate(&mut conn, Path::new(":memory:")).unwrap();
+        assert_eq!(current_version(&conn).unwrap(), latest_version(MIGRATIONS));
+    }
+
+    /// A database from before versioned migrations: the tables exist, but
+    /// `user_version` is still 0.
+    #[test]
+    fn unversioned_database() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "
+            CREATE TABLE projects (
+                id INTEGER PRIMARY KEY,
+                name TEXT UNIQUE NOT NULL
+            );
+            CREATE TABLE tags (
+                id INTEGER PRIMARY KEY,
+                name TEXT UNIQUE NOT NULL
+            );
+            CREATE TABLE timers (
+                id INTEGER PRIMARY KEY,
+                rid TEXT NOT NULL,
+                start TEXT NOT NULL,
+                end TEXT,
+                note TEXT
+            );
+            CREATE TABLE tags_timers (
+                tag_id INTEGER NOT NULL,
+                timer_id INTEGER NOT NULL
+            );
+            CREATE TABLE projects_timers (
+                project_id INTEGER NOT NULL,
+                timer_id INTEGER NOT NULL
+            );
+            INSERT INTO projects (name) VALUES ('book');
+            INSERT INTO timers (rid, start, end) VALUES
+                ('abc', '2020-01-05T10:00:00Z', '2020-01-05T11:00:00Z');
+            INSERT INTO projects_timers (project_id, timer_id) VALUES (1, 1);
+            ",
+        )
+        .unwrap();
+
+        migrate(&mut conn, Path::new(":memory:")).unwrap();
+
+        assert_eq!(current_version(&conn).unwrap(), latest_version(MIGRATIONS));
+        assert_eq!(count(&conn, "timers"), 1);
+        assert_eq!(count(&conn, "timer_segments"), 1);
+        let color: Option<String> = conn
+            .query_row("SELECT color FROM projects", NO_PARAMS, |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(color, None);
+    }
+
+    #[test]
+    fn newer_database() {
+        let newer = latest_version(MIGRATIONS) + 1;
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", &newer).unwrap();
+
+        assert!(migrate(&mut conn, Path::new(":memory:")).is_err());
+        assert_eq!(current_version(&conn).unwrap(), newer);
+    }
+
+    #[test]
+    fn destructive_migration_backs_up() {
+        let dir = env::temp_dir()
+            .join(format!("faramir-migrations-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("faramir.db");
+        let mut conn = Connection::open(&db_path).unwrap();
+
+        let migrations = [
+            Migration {
+                version:     1,
+                description: "table",
+                destructive: false,
+                sql:         "CREATE TABLE items (name TEXT);",
+            },
+            Migration {
+                version:     2,
+                description: "drop table",
+                destructive: true,
+                sql:         "DROP TABLE items;",
+            },
+        ];
+        let backups = apply(&mut conn, &db_path, &migrations).unwrap();
+
+        assert_eq!(backups.len(), 1);
+        let backup = Connection::open(&backups[0]).unwrap();
+        assert_eq!(current_version(&backup).unwrap(), 1);
+        assert!(tables(&backup).contains(&"items".to_string()));
+        assert!(!tables(&conn).contains(&"items".to_string()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,9 +3,14 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 
-use crate::errors::{AppError, AppResult};
+use crate::{
+    errors::{AppError, AppResult},
+    utils,
+};
 
 #[derive(Deserialize, Serialize)]
 pub struct Config {
@@ -13,6 +18,9 @@ pub struct Config {
     pub time_format:      String,
     pub full_time_format: String,
     pub timezone:         String,
+    /// Starting a timer stops any running ones.
+    #[serde(default)]
+    pub single_active:    bool,
 }
 
 const FARAMIR_DIR: &str = "faramir-tt";
@@ -29,11 +37,31 @@ impl Default for Config {
             time_format,
             full_time_format,
             timezone,
+            single_active: false,
         }
     }
 }
 
 impl Config {
+    /// The timezone times are shown and entered in.
+    pub fn tz(&self) -> AppResult<Tz> {
+        self.timezone.parse().map_err(AppError::from)
+    }
+
+    /// Formats a stored UTC time for display, in `timezone` and with
+    /// `time_format`, or `full_time_format` when `detailed`. Every time shown
+    /// to the user goes through here.
+    pub fn format_time(&self, dt: &DateTime<Utc>, detailed: bool) -> String {
+        // main() checks the timezone before running any command.
+        let tz = self.tz().unwrap_or(Tz::UTC);
+        let format = match detailed {
+            true => &self.full_time_format,
+            false => &self.time_format,
+        };
+
+        utils::format_time(dt, &tz, format)
+    }
+
     pub fn default_config_dir() -> PathBuf {
         let config_path;
 
@@ -65,7 +93,6 @@ impl Config {
         let json = serde_json::to_string_pretty(&config)?;
 
         fs::write(path, &json)?;
-        println!("File created at {}.", &path.display());
 
         Ok(config)
     }
@@ -1,4 +1,5 @@
 pub mod config;
 pub mod project;
+pub mod segment;
 pub mod tag;
 pub mod timer;
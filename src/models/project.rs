@@ -1,12 +1,14 @@
 use std::collections::HashMap;
 
-use rusqlite::{params, Connection, NO_PARAMS};
+use rusqlite::{params, Connection, Row, NO_PARAMS};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     errors::{AppError, AppResult},
-    models::timer::Timers,
+    query::{self, FromRow},
 };
 
+#[derive(Default)]
 pub struct Projects(pub Vec<Project>);
 
 impl Projects {
@@ -14,68 +16,63 @@ impl Projects {
         self.0.len()
     }
 
-    pub fn print_basic(self) {
-        println!("{} Project(s) found.", self.len());
-        println!(
-            "{}",
-            self.0
-                .into_iter()
-                .map(|p| p.name)
-                .collect::<Vec<String>>()
-                .join(", ")
-        );
-    }
-
-    pub fn print_detailed(&self, conn: &Connection) -> AppResult<()> {
-        println!("{} Project(s) found.", self.len());
-
-        let mut project_timers = HashMap::new();
-
-        for project in &self.0 {
-            let timers = Timers::for_project(&conn, project.id)?;
-            project_timers.insert(&project.name, timers);
-        }
-
-        for project in &self.0 {
-            println!(
-                "{} - {} timer(s) found.",
-                &project.name,
-                project_timers.get(&project.name).unwrap().len()
-            )
-        }
-
-        Ok(())
-    }
-
-    pub fn default() -> Self {
-        Projects(vec![])
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 
     pub fn push(&mut self, project: Project) {
         self.0.push(project)
     }
 
-    pub fn all(conn: &Connection) -> AppResult<Self> {
-        let mut stmt = conn.prepare("SELECT * FROM projects ORDER BY name")?;
-        let project_iter = stmt.query_map(NO_PARAMS, |row| {
-            Ok(Project {
-                id:   row.get(0)?,
-                name: row.get(1)?,
-            })
+    /// Maps every timer id to the name of its project, in a single query.
+    pub fn names_by_timer(
+        conn: &Connection,
+    ) -> AppResult<HashMap<i32, String>> {
+        let sql = "SELECT pt.timer_id, p.name FROM projects_timers pt JOIN \
+                   projects p ON p.id = pt.project_id";
+        let mut stmt = conn.prepare(sql)?;
+        let row_iter = stmt.query_map(NO_PARAMS, |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?))
         })?;
 
-        let mut projects = Projects::default();
-        for project in project_iter {
-            projects.push(project?);
+        let mut names = HashMap::new();
+        for row in row_iter {
+            let (timer_id, name) = row?;
+            names.insert(timer_id, name);
         }
 
-        Ok(projects)
+        Ok(names)
+    }
+
+    pub fn all(conn: &Connection) -> AppResult<Self> {
+        query::all(conn, "SELECT * FROM projects ORDER BY name", NO_PARAMS)
+            .map(Projects)
     }
 }
 
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Project {
-    pub id:   i32,
-    pub name: String,
+    pub id:          i32,
+    pub name:        String,
+    pub description: Option<String>,
+    pub color:       Option<String>,
+    pub archived:    bool,
+    pub hourly_rate: Option<f64>,
+}
+
+impl FromRow for Project {
+    const COLUMNS: usize = 6;
+
+    fn from_row(row: &Row, offset: usize) -> rusqlite::Result<Self> {
+        Ok(Project {
+            id:          row.get(offset)?,
+            name:        row.get(offset + 1)?,
+            description: row.get(offset + 2)?,
+            color:       row.get(offset + 3)?,
+            archived:    row.get(offset + 4)?,
+            hourly_rate: row.get(offset + 5)?,
+        })
+    }
 }
 
 impl Project {
@@ -93,34 +90,30 @@ impl Project {
         .map_err(|e| AppError::from(e))
     }
 
-    pub fn update(&self, conn: &Connection, new_name: &str) -> AppResult<()> {
-        let sql = "UPDATE projects SET name=?2 WHERE name = ?1";
-        conn.execute(&sql, params![self.name, new_name])?;
+    pub fn update(&self, conn: &Connection) -> AppResult<()> {
+        let sql = "UPDATE projects SET name=?2,description=?3,color=?4,\
+                   archived=?5,hourly_rate=?6 WHERE id = ?1";
+        conn.execute(
+            sql,
+            params![
+                self.id,
+                self.name,
+                self.description,
+                self.color,
+                self.archived,
+                self.hourly_rate
+            ],
+        )?;
 
         Ok(())
     }
 
     pub fn find_by_name(conn: &Connection, name: &str) -> AppResult<Project> {
-        let mut stmt =
-            conn.prepare("SELECT * FROM projects WHERE name = ?1")?;
-        stmt.query_row(&[name], |row| {
-            Ok(Project {
-                id:   row.get(0)?,
-                name: row.get(1)?,
-            })
-        })
-        .map_err(|e| AppError::from(e))
+        query::one(conn, "SELECT * FROM projects WHERE name = ?1", [name])
     }
 
     pub fn find(conn: &Connection, project_id: i32) -> AppResult<Project> {
-        let mut stmt = conn.prepare("SELECT * FROM projects WHERE id = ?1")?;
-        stmt.query_row(&[project_id], |row| {
-            Ok(Project {
-                id:   row.get(0)?,
-                name: row.get(1)?,
-            })
-        })
-        .map_err(|e| AppError::from(e))
+        query::one(conn, "SELECT * FROM projects WHERE id = ?1", [project_id])
     }
 
     pub fn for_timer(conn: &Connection, timer_id: i32) -> AppResult<Project> {
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, Row, NO_PARAMS};
+
+use crate::{
+    errors::{AppError, AppResult},
+    query::{self, FromRow},
+};
+
+/// One uninterrupted stretch of a timer. A timer gets a new segment every
+/// time it's resumed after a pause.
+#[derive(Debug)]
+pub struct Segment {
+    pub id:       i32,
+    pub timer_id: i32,
+    pub start:    DateTime<Utc>,
+    pub end:      Option<DateTime<Utc>>,
+}
+
+impl FromRow for Segment {
+    const COLUMNS: usize = 4;
+
+    fn from_row(row: &Row, offset: usize) -> rusqlite::Result<Self> {
+        Ok(Segment {
+            id:       row.get(offset)?,
+            timer_id: row.get(offset + 1)?,
+            start:    row.get(offset + 2)?,
+            end:      row.get(offset + 3)?,
+        })
+    }
+}
+
+impl Segment {
+    pub fn duration(&self) -> Duration {
+        match self.end {
+            Some(end) => end.signed_duration_since(self.start),
+            None => Utc::now().signed_duration_since(self.start),
+        }
+    }
+
+    pub fn insert(
+        conn: &Connection, timer_id: i32, start: DateTime<Utc>,
+        end: Option<DateTime<Utc>>,
+    ) -> AppResult<usize> {
+        conn.execute(
+            "INSERT INTO timer_segments (timer_id, start, end) VALUES (?1, \
+             ?2, ?3)",
+            params![timer_id, start, end],
+        )
+        .map_err(AppError::from)
+    }
+}
+
+/// The start and end of a segment that isn't saved yet.
+pub type Bounds = (DateTime<Utc>, Option<DateTime<Utc>>);
+
+pub struct Segments(pub Vec<Segment>);
+
+impl Segments {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.0
+            .iter()
+            .fold(Duration::zero(), |acc, s| acc + s.duration())
+    }
+
+    pub fn last(&self) -> Option<&Segment> {
+        self.0.last()
+    }
+
+    /// Loads a timer's segments, oldest first.
+    pub fn for_timer(conn: &Connection, timer_id: i32) -> AppResult<Self> {
+        let sql =
+            "SELECT * FROM timer_segments WHERE timer_id = ?1 ORDER BY start";
+        query::all(conn, sql, [timer_id]).map(Segments)
+    }
+
+    /// Maps every timer id to its segments, oldest first, in a single query.
+    pub fn by_timer(conn: &Connection) -> AppResult<HashMap<i32, Segments>> {
+        let sql = "SELECT * FROM timer_segments ORDER BY timer_id, start";
+        let segments: Vec<Segment> = query::all(conn, sql, NO_PARAMS)?;
+
+        let mut by_timer: HashMap<i32, Segments> = HashMap::new();
+        for segment in segments {
+            by_timer
+                .entry(segment.timer_id)
+                .or_insert_with(|| Segments(vec![]))
+                .0
+                .push(segment);
+        }
+
+        Ok(by_timer)
+    }
+
+    /// Replaces a timer's segments, e.g. with the ones of an imported timer.
+    pub fn replace(
+        conn: &Connection, timer_id: i32, segments: &[Bounds],
+    ) -> AppResult<()> {
+        conn.execute(
+            "DELETE FROM timer_segments WHERE timer_id = ?1",
+            params![timer_id],
+        )?;
+        for (start, end) in segments {
+            Segment::insert(conn, timer_id, *start, *end)?;
+        }
+
+        Ok(())
+    }
+
+    /// Fits a timer's segments into its new `start` and `end` after an edit:
+    /// segments outside of it are dropped, and the first and last ones are
+    /// stretched or cut to line up with it. A running timer's last segment is
+    /// left alone, so a paused timer stays paused.
+    ///
+    /// When dropping segments leaves a pause at either edge, e.g. after
+    /// `stop --at` a time before the last resume, the remaining segment is
+    /// only cut, never stretched across the pause.
+    pub fn fit_to(
+        conn: &Connection, timer_id: i32, start: DateTime<Utc>,
+        end: Option<DateTime<Utc>>,
+    ) -> AppResult<()> {
+        let dropped_before = conn.execute(
+            "DELETE FROM timer_segments WHERE timer_id = ?1 AND end <= ?2",
+            params![timer_id, start],
+        )?;
+        let dropped_after = conn.execute(
+            "DELETE FROM timer_segments WHERE timer_id = ?1 AND start >= ?2",
+            params![timer_id, end],
+        )?;
+
+        let segments = Segments::for_timer(conn, timer_id)?;
+        let (first, last) = match (segments.0.first(), segments.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => {
+                Segment::insert(conn, timer_id, start, end)?;
+                return Ok(());
+            },
+        };
+
+        let first_start = match dropped_before {
+            0 => start,
+            _ => first.start.max(start),
+        };
+        conn.execute(
+            "UPDATE timer_segments SET start = ?2 WHERE id = ?1",
+            params![first.id, first_start],
+        )?;
+        if let Some(end) = end {
+            let last_end = match (dropped_after, last.end) {
+                (0, _) | (_, None) => end,
+                (_, Some(last_end)) => last_end.min(end),
+            };
+            conn.execute(
+                "UPDATE timer_segments SET end = ?2 WHERE id = ?1",
+                params![last.id, last_end],
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,7 +1,14 @@
-use rusqlite::{params, Connection, NO_PARAMS};
+use std::collections::HashMap;
 
-use crate::errors::{AppError, AppResult};
+use rusqlite::{params, Connection, Row, NO_PARAMS};
+use serde::{Deserialize, Serialize};
 
+use crate::{
+    errors::{AppError, AppResult},
+    query::{self, FromRow},
+};
+
+#[derive(Default)]
 pub struct Tags(pub Vec<Tag>);
 
 impl Tags {
@@ -9,6 +16,10 @@ impl Tags {
         self.0.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub fn ids(&self) -> AppResult<Vec<i32>> {
         let ids = self.0.iter().map(|t| t.id).collect::<Vec<i32>>();
 
@@ -23,114 +34,103 @@ impl Tags {
         Tags(tags)
     }
 
-    pub fn default() -> Self {
-        Tags(vec![])
+    pub fn all(conn: &Connection) -> AppResult<Self> {
+        query::all(conn, "SELECT * FROM tags ORDER BY name", NO_PARAMS)
+            .map(Tags::new)
     }
 
-    pub fn all(conn: &Connection) -> AppResult<Self> {
-        let mut stmt = conn.prepare("SELECT * FROM tags ORDER BY name")?;
-        let tag_iter = stmt.query_map(NO_PARAMS, |row| {
-            Ok(Tag {
-                id:   row.get(0)?,
-                name: row.get(1)?,
-            })
+    /// Maps every tagged timer id to its tag names, in a single query.
+    pub fn names_by_timer(
+        conn: &Connection,
+    ) -> AppResult<HashMap<i32, Vec<String>>> {
+        let sql = "SELECT tt.timer_id, ta.name FROM tags_timers tt JOIN tags \
+                   ta ON ta.id = tt.tag_id ORDER BY ta.name";
+        let mut stmt = conn.prepare(sql)?;
+        let row_iter = stmt.query_map(NO_PARAMS, |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?))
         })?;
 
-        let mut tags = Tags::default();
-        for tag in tag_iter {
-            tags.0.push(tag?);
+        let mut names: HashMap<i32, Vec<String>> = HashMap::new();
+        for row in row_iter {
+            let (timer_id, name) = row?;
+            names.entry(timer_id).or_default().push(name);
         }
 
-        Ok(tags)
+        Ok(names)
     }
 
-    pub fn load(conn: &Connection, names: Vec<String>) -> AppResult<Self> {
-        let joined = names
-            .iter()
-            .map(|n| format!("\"{}\"", n))
-            .collect::<Vec<String>>()
-            .join(",");
-
-        let mut stmt = conn.prepare(&format!(
-            "select * from tags where name in ({})",
-            joined
-        ))?;
-        let tag_iter = stmt.query_map(NO_PARAMS, |row| {
-            Ok(Tag {
-                id:   row.get(0)?,
-                name: row.get(1)?,
-            })
-        })?;
-
-        let mut tags = Vec::new();
-        for tag in tag_iter {
-            tags.push(tag?);
-        }
-
-        Ok(Tags::new(tags))
+    pub fn load(conn: &Connection, names: &[String]) -> AppResult<Self> {
+        let sql = format!(
+            "SELECT * FROM tags WHERE name IN ({})",
+            query::placeholders(names.len())
+        );
+        query::all(conn, &sql, names).map(Tags::new)
     }
 
     pub fn for_timer(conn: &Connection, timer_id: i32) -> AppResult<Self> {
         let sql = "SELECT ta.* FROM tags ta JOIN tags_timers tt ON tt.tag_id \
                    = ta.id WHERE tt.timer_id = ?1";
-        let mut stmt = conn.prepare(sql)?;
-        let tag_iter = stmt.query_map(&[timer_id], |row| {
-            Ok(Tag {
-                id:   row.get(0)?,
-                name: row.get(1)?,
-            })
-        })?;
-
-        let mut tags = vec![];
-        for tag in tag_iter {
-            tags.push(tag?);
-        }
-
-        Ok(Tags::new(tags))
+        query::all(conn, sql, [timer_id]).map(Tags::new)
     }
 }
 
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Tag {
-    pub id:   i32,
-    pub name: String,
+    pub id:          i32,
+    pub name:        String,
+    pub description: Option<String>,
+    pub color:       Option<String>,
+}
+
+impl FromRow for Tag {
+    const COLUMNS: usize = 4;
+
+    fn from_row(row: &Row, offset: usize) -> rusqlite::Result<Self> {
+        Ok(Tag {
+            id:          row.get(offset)?,
+            name:        row.get(offset + 1)?,
+            description: row.get(offset + 2)?,
+            color:       row.get(offset + 3)?,
+        })
+    }
 }
 
 impl Tag {
+    /// Creates the tags that don't exist yet and returns every tag's id. Run
+    /// it in a transaction to save each insert a commit.
     pub fn batch_insert(
-        conn: &mut Connection, names: Vec<String>,
+        conn: &Connection, names: &[String],
     ) -> AppResult<Vec<i32>> {
-        let tx = conn.transaction()?;
-        for name in &names {
-            tx.execute(
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+
+        for name in names {
+            conn.execute(
                 "INSERT OR IGNORE INTO tags (name) VALUES (?1)",
                 &[name],
             )?;
         }
-        tx.commit()?;
 
-        Tags::load(&conn, names)?.ids()
+        Tags::load(conn, names)?.ids()
     }
 
     pub fn find_by_name(conn: &Connection, name: &str) -> AppResult<Self> {
-        let mut stmt = conn.prepare("SELECT * FROM tags WHERE name = ?1")?;
-        stmt.query_row(&[name], |row| {
-            Ok(Tag {
-                id:   row.get(0)?,
-                name: row.get(1)?,
-            })
-        })
-        .map_err(|e| AppError::from(e))
+        query::one(conn, "SELECT * FROM tags WHERE name = ?1", [name])
     }
 
-    #[allow(dead_code)]
     pub fn insert(conn: &Connection, name: &str) -> AppResult<usize> {
         conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", &[name])
             .map_err(|e| AppError::from(e))
     }
 
-    pub fn update(&self, conn: &Connection, new_name: &str) -> AppResult<()> {
-        let sql = "UPDATE tags SET name=?2 WHERE name = ?1";
-        conn.execute(&sql, params![self.name, new_name])?;
+    pub fn update(&self, conn: &Connection) -> AppResult<()> {
+        let sql =
+            "UPDATE tags SET name=?2,description=?3,color=?4 WHERE id = ?1";
+        conn.execute(
+            sql,
+            params![self.id, self.name, self.description, self.color],
+        )?;
 
         Ok(())
     }
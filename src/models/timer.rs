@@ -1,30 +1,48 @@
+use std::collections::HashMap;
+
 use chrono::{DateTime, Duration, Utc};
-use rusqlite::{params, Connection, NO_PARAMS};
+use rusqlite::{params, Connection, Row, NO_PARAMS};
 use serde::{Deserialize, Serialize};
 
 use crate::{
     errors::{AppError, AppResult},
-    models::config::Config,
-    utils::{format_seconds, rand_string},
+    filter::TimerFilter,
+    models::{
+        project::Project,
+        segment::{Segment, Segments},
+        tag::Tags,
+    },
+    query::{self, FromRow, Order, TimerColumn, TimerQuery},
+    utils::rand_string,
 };
 
-#[derive(Debug)]
-#[allow(dead_code)]
+#[derive(Debug, Default)]
 pub struct Timers(pub Vec<Timer>);
 impl Timers {
-    pub fn total_seconds(&self) -> i64 {
+    /// The timers' summed durations, given every timer's segments from
+    /// `Segments::by_timer`.
+    pub fn total_seconds(&self, segments: &HashMap<i32, Segments>) -> i64 {
         self.0
             .iter()
-            .fold(0, |acc, t| acc + t.duration().num_seconds())
+            .map(|timer| {
+                timer.duration_of(segments.get(&timer.id)).num_seconds()
+            })
+            .sum()
     }
 
     pub fn len(&self) -> usize {
         self.0.len()
     }
 
-    pub fn stop_all(&mut self, conn: &Connection) -> AppResult<()> {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn stop_all(
+        &mut self, conn: &Connection, end: DateTime<Utc>,
+    ) -> AppResult<()> {
         for timer in &mut self.0 {
-            &timer.stop(&conn)?;
+            timer.stop(conn, end)?;
         }
 
         Ok(())
@@ -34,11 +52,6 @@ impl Timers {
         Timers(timers)
     }
 
-    #[allow(dead_code)]
-    pub fn default() -> Self {
-        Timers(vec![])
-    }
-
     pub fn batch_delete(self, conn: &mut Connection) -> AppResult<()> {
         let tx = conn.transaction()?;
         for timer in self.0 {
@@ -46,6 +59,10 @@ impl Timers {
                 "DELETE FROM tags_timers WHERE timer_id = ?1",
                 &[&timer.id],
             )?;
+            tx.execute(
+                "DELETE FROM timer_segments WHERE timer_id = ?1",
+                &[&timer.id],
+            )?;
             tx.execute("DELETE FROM timers WHERE id = ?1", &[&timer.id])?;
         }
         tx.commit().map_err(|e| AppError::from(e))
@@ -55,84 +72,100 @@ impl Timers {
     pub fn for_project(conn: &Connection, project_id: i32) -> AppResult<Self> {
         let sql = "SELECT t.* FROM projects_timers pt JOIN timers t ON \
                    pt.timer_id = t.id WHERE pt.project_id = ?1";
-        let mut stmt = conn.prepare(&sql)?;
-        let timer_iter = stmt.query_map(&[project_id], |row| {
-            Ok(Timer {
-                id:    row.get(0)?,
-                rid:   row.get(1)?,
-                start: row.get(2)?,
-                end:   row.get(3)?,
-                note:  row.get(4)?,
-            })
-        })?;
-
-        let mut timers = vec![];
-        for timer in timer_iter {
-            timers.push(timer?)
-        }
-
-        Ok(Timers::new(timers))
+        query::all(conn, sql, [project_id]).map(Timers::new)
     }
 
     pub fn for_tag(conn: &Connection, project_id: i32) -> AppResult<Self> {
         let sql = "SELECT t.* FROM tags_timers tt JOIN timers t ON \
                    tt.timer_id = t.id WHERE tt.tag_id = ?1";
-        let mut stmt = conn.prepare(&sql)?;
-        let timer_iter = stmt.query_map(&[project_id], |row| {
-            Ok(Timer {
-                id:    row.get(0)?,
-                rid:   row.get(1)?,
-                start: row.get(2)?,
-                end:   row.get(3)?,
-                note:  row.get(4)?,
-            })
-        })?;
+        query::all(conn, sql, [project_id]).map(Timers::new)
+    }
 
-        let mut timers = vec![];
-        for timer in timer_iter {
-            timers.push(timer?)
-        }
+    pub fn all(conn: &Connection) -> AppResult<Self> {
+        TimerQuery::new()
+            .order_by(TimerColumn::Start, Order::Asc)
+            .fetch(conn)
+    }
 
-        Ok(Timers::new(timers))
+    pub fn currently_running(conn: &Connection) -> AppResult<Self> {
+        TimerQuery::new()
+            .running(true)
+            .order_by(TimerColumn::Start, Order::Asc)
+            .fetch(conn)
     }
 
-    pub fn load(conn: &Connection, extra: &str) -> AppResult<Self> {
-        let sql = format!("SELECT * FROM timers {}", extra);
-        let mut stmt = conn.prepare(&sql)?;
-        let timer_iter = stmt.query_map(params![], |row| {
-            Ok(Timer {
-                id:    row.get(0)?,
-                rid:   row.get(1)?,
-                start: row.get(2)?,
-                end:   row.get(3)?,
-                note:  row.get(4)?,
-            })
-        })?;
+    pub fn finished(conn: &Connection) -> AppResult<Self> {
+        TimerQuery::new()
+            .running(false)
+            .order_by(TimerColumn::Start, Order::Asc)
+            .fetch(conn)
+    }
+
+    /// Loads the timers matching `filter`, oldest first. When a limit is
+    /// set, the most recent timers are kept.
+    pub fn filter(conn: &Connection, filter: &TimerFilter) -> AppResult<Self> {
+        let mut query = TimerQuery::new();
 
-        let mut timers = vec![];
-        for timer in timer_iter {
-            timers.push(timer?)
+        if !filter.include_running {
+            query = query.running(false);
+        }
+        if let Some(from) = filter.from {
+            query = query.started_from(from);
+        }
+        if let Some(to) = filter.to {
+            query = query.started_before(to);
+        }
+        if !filter.projects.is_empty() {
+            query = query.projects(&filter.projects);
+        }
+        if !filter.tags.is_empty() {
+            query = query.tags(&filter.tags);
         }
 
-        Ok(Timers::new(timers))
-    }
+        query = query.order_by(TimerColumn::Start, Order::Desc);
+        if let Some(limit) = filter.limit {
+            query = query.limit(limit);
+        }
 
-    #[allow(dead_code)]
-    pub fn all(conn: &Connection) -> AppResult<Self> {
-        Timers::load(&conn, "")
+        let mut timers = query.fetch(conn)?;
+        timers.0.reverse();
+
+        Ok(timers)
     }
 
-    pub fn currently_running(conn: &Connection) -> AppResult<Self> {
-        Timers::load(&conn, "WHERE end IS NULL")
+    /// Loads the timers overlapping `[start, end)`, other than `exclude_id`.
+    /// Running timers, and a missing `end`, count as never ending.
+    pub fn overlapping(
+        conn: &Connection, start: DateTime<Utc>, end: Option<DateTime<Utc>>,
+        exclude_id: Option<i32>,
+    ) -> AppResult<Self> {
+        let sql = "SELECT * FROM timers WHERE (end IS NULL OR end > ?1) AND \
+                   (?2 IS NULL OR start < ?2) AND id != ?3 ORDER BY start";
+        query::all(conn, sql, params![start, end, exclude_id.unwrap_or(-1)])
+            .map(Timers::new)
     }
 
-    #[allow(dead_code)]
-    pub fn finished(conn: &Connection) -> AppResult<Self> {
-        Timers::load(&conn, "WHERE end IS NOT NULL")
+    /// Fails with `ErrorKind::Overlap` when `[start, end)` overlaps a timer
+    /// other than `exclude_id`.
+    pub fn check_overlaps(
+        conn: &Connection, start: DateTime<Utc>, end: Option<DateTime<Utc>>,
+        exclude_id: Option<i32>,
+    ) -> AppResult<()> {
+        let overlapping = Timers::overlapping(conn, start, end, exclude_id)?;
+        match overlapping.len() {
+            0 => Ok(()),
+            _ => Err(AppError::overlap(overlapping)),
+        }
     }
 
-    pub fn limit(conn: &Connection, limit: &str) -> AppResult<Self> {
-        Timers::load(&conn, &format!("WHERE end is NOT NULL LIMIT {}", limit))
+    /// Finds every pair of timers whose intervals overlap, the earlier one
+    /// first.
+    pub fn overlap_pairs(conn: &Connection) -> AppResult<Vec<(Timer, Timer)>> {
+        let sql = "SELECT a.*, b.* FROM timers a JOIN timers b ON (a.start < \
+                   b.start OR (a.start = b.start AND a.id < b.id)) WHERE \
+                   (a.end IS NULL OR a.end > b.start) ORDER BY a.start, \
+                   b.start";
+        query::all(conn, sql, NO_PARAMS)
     }
 }
 
@@ -145,74 +178,202 @@ pub struct Timer {
     pub note:  Option<String>,
 }
 
+impl FromRow for Timer {
+    const COLUMNS: usize = 5;
+
+    fn from_row(row: &Row, offset: usize) -> rusqlite::Result<Self> {
+        Ok(Timer {
+            id:    row.get(offset)?,
+            rid:   row.get(offset + 1)?,
+            start: row.get(offset + 2)?,
+            end:   row.get(offset + 3)?,
+            note:  row.get(offset + 4)?,
+        })
+    }
+}
+
 impl Timer {
-    pub fn duration(&self) -> Duration {
+    /// The time spent on the timer, leaving out any pauses.
+    pub fn duration(&self, conn: &Connection) -> AppResult<Duration> {
+        let segments = Segments::for_timer(conn, self.id)?;
+        Ok(self.duration_of(Some(&segments)))
+    }
+
+    /// `duration`, from segments loaded up front with `Segments::by_timer`.
+    pub fn duration_of(&self, segments: Option<&Segments>) -> Duration {
+        match (segments, self.end) {
+            (Some(segments), _) if !segments.is_empty() => segments.duration(),
+            (_, Some(end)) => end.signed_duration_since(self.start),
+            (_, None) => Utc::now().signed_duration_since(self.start),
+        }
+    }
+
+    /// When the timer was paused, if it's paused right now.
+    pub fn paused_since(
+        &self, conn: &Connection,
+    ) -> AppResult<Option<DateTime<Utc>>> {
+        let segments = Segments::for_timer(conn, self.id)?;
+        Ok(self.paused_since_of(Some(&segments)))
+    }
+
+    /// `paused_since`, from segments loaded up front with
+    /// `Segments::by_timer`.
+    pub fn paused_since_of(
+        &self, segments: Option<&Segments>,
+    ) -> Option<DateTime<Utc>> {
         match self.end {
-            Some(end) => end.signed_duration_since(self.start),
-            None => Utc::now().signed_duration_since(self.start),
+            Some(_) => None,
+            None => segments?.last()?.end,
         }
     }
 
-    pub fn pretty_print(&self, config: &Config, is_detailed: bool) {
-        let duration = match self.end {
-            Some(end) => end.signed_duration_since(self.start),
-            None => Utc::now().signed_duration_since(self.start),
-        };
-        let format_elapsed = format_seconds(duration.num_seconds());
-        let format_start = match is_detailed {
-            true => self.start.format(&config.full_time_format),
-            false => self.start.format(&config.time_format),
+    pub fn update(&self, conn: &Connection) -> AppResult<()> {
+        let sql = "UPDATE timers SET start=?2,end=?3,note=?4 WHERE id = ?1";
+        conn.execute(sql, params![self.id, self.start, self.end, self.note])?;
+        Segments::fit_to(conn, self.id, self.start, self.end)
+    }
+
+    /// Deletes the timer along with its project and tag associations.
+    pub fn delete(&self, conn: &mut Connection) -> AppResult<()> {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "DELETE FROM projects_timers WHERE timer_id = ?1",
+            params![self.id],
+        )?;
+        tx.execute(
+            "DELETE FROM tags_timers WHERE timer_id = ?1",
+            params![self.id],
+        )?;
+        tx.execute(
+            "DELETE FROM timer_segments WHERE timer_id = ?1",
+            params![self.id],
+        )?;
+        tx.execute("DELETE FROM timers WHERE id = ?1", params![self.id])?;
+        tx.commit().map_err(AppError::from)
+    }
+
+    /// Stops the timer at `end`. A paused timer ends where it was paused.
+    pub fn stop(
+        &mut self, conn: &Connection, end: DateTime<Utc>,
+    ) -> AppResult<()> {
+        // A paused timer ends when it was paused, unless it's stopped even
+        // earlier, in which case `update` cuts its segments at `end`.
+        let end = match self.paused_since(conn)? {
+            Some(paused_since) => paused_since.min(end),
+            None => end,
         };
+        if end < self.start {
+            return Err(AppError::from_str(&format!(
+                "Timer {} can't be stopped before it started.",
+                self.rid
+            )));
+        }
 
-        println!("  Elapsed Time: {}", format_elapsed);
-        println!("  Start Time: {}", format_start);
+        self.end = Some(end);
+        self.update(&conn)?;
+
+        Ok(())
     }
 
-    pub fn update(&self, conn: &Connection) -> AppResult<()> {
-        let sql = "UPDATE timers SET start=?2,end=?3 WHERE id = ?1";
-        conn.execute(&sql, params![self.id, self.start, self.end])?;
+    pub fn pause(&self, conn: &Connection, at: DateTime<Utc>) -> AppResult<()> {
+        let segments = Segments::for_timer(conn, self.id)?;
+        let segment = match segments.last() {
+            Some(segment) if self.end.is_none() && segment.end.is_none() => {
+                segment
+            },
+            _ => {
+                return Err(AppError::from_str(&format!(
+                    "Timer {} isn't running.",
+                    self.rid
+                )))
+            },
+        };
+
+        if at < segment.start {
+            return Err(AppError::from_str(&format!(
+                "Timer {} can't be paused before it was last started.",
+                self.rid
+            )));
+        }
+
+        conn.execute(
+            "UPDATE timer_segments SET end = ?2 WHERE id = ?1",
+            params![segment.id, at],
+        )?;
 
         Ok(())
     }
 
-    pub fn stop(&mut self, conn: &Connection) -> AppResult<()> {
-        self.end = Some(Utc::now());
-        self.update(&conn)?;
-        println!("Stopped timer {}.", self.rid);
+    pub fn resume(
+        &self, conn: &Connection, at: DateTime<Utc>,
+    ) -> AppResult<()> {
+        let paused = match self.paused_since(conn)? {
+            Some(paused) => paused,
+            None => {
+                return Err(AppError::from_str(&format!(
+                    "Timer {} isn't paused.",
+                    self.rid
+                )))
+            },
+        };
+
+        if at < paused {
+            return Err(AppError::from_str(&format!(
+                "Timer {} can't be resumed before it was paused.",
+                self.rid
+            )));
+        }
+
+        Segment::insert(conn, self.id, at, None)?;
 
         Ok(())
     }
 
     pub fn find_by(
-        conn: &Connection, column: &str, val: &str,
+        conn: &Connection, column: TimerColumn, val: &str,
     ) -> AppResult<Timer> {
-        let sql = format!("SELECT * FROM timers WHERE {} = ?1", column);
-        let mut stmt = conn.prepare(&sql)?;
-        stmt.query_row(&[val], |row| {
-            Ok(Timer {
-                id:    row.get(0)?,
-                rid:   row.get(1)?,
-                start: row.get(2)?,
-                end:   row.get(3)?,
-                note:  row.get(4)?,
-            })
-        })
-        .map_err(|e| AppError::from(e))
+        TimerQuery::new().eq(column, val.to_string()).fetch_one(conn)
     }
 
     pub fn last(conn: &Connection) -> AppResult<Timer> {
-        let sql = format!("SELECT * FROM timers ORDER BY id DESC");
-        let mut stmt = conn.prepare(&sql)?;
-        stmt.query_row(NO_PARAMS, |row| {
-            Ok(Timer {
-                id:    row.get(0)?,
-                rid:   row.get(1)?,
-                start: row.get(2)?,
-                end:   row.get(3)?,
-                note:  row.get(4)?,
-            })
+        TimerQuery::new()
+            .order_by(TimerColumn::Id, Order::Desc)
+            .fetch_one(conn)
+    }
+
+    /// The most recently started timer of a project.
+    pub fn last_for_project(
+        conn: &Connection, project_id: i32,
+    ) -> AppResult<Timer> {
+        let sql = "SELECT t.* FROM projects_timers pt JOIN timers t ON \
+                   pt.timer_id = t.id WHERE pt.project_id = ?1 ORDER BY \
+                   t.start DESC";
+        query::one(conn, sql, [project_id])
+    }
+}
+
+/// A timer along with its project and tag names, as it's shown when editing
+/// in $EDITOR.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EditTimer {
+    #[serde(flatten)]
+    pub timer:   Timer,
+    pub project: String,
+    #[serde(default)]
+    pub tags:    Vec<String>,
+}
+
+impl EditTimer {
+    pub fn load(conn: &Connection, rid: &str) -> AppResult<Self> {
+        let timer = Timer::find_by(conn, TimerColumn::Rid, rid)?;
+        let project = Project::for_timer(conn, timer.id)?;
+        let tags = Tags::for_timer(conn, timer.id)?;
+
+        Ok(EditTimer {
+            timer,
+            project: project.name,
+            tags: tags.names(),
         })
-        .map_err(|e| AppError::from(e))
     }
 }
 
@@ -236,18 +397,13 @@ impl CreateTimer {
         }
     }
 
-    pub fn default() -> Self {
-        CreateTimer {
-            rid:   rand_string(12),
-            start: Utc::now(),
-            end:   None,
-            note:  None,
+    pub fn insert_and_get_id(&self, conn: &Connection) -> AppResult<i32> {
+        let inserted = self.insert(conn)?;
+        let timer = Timer::find_by(conn, TimerColumn::Rid, &self.rid)?;
+        if inserted > 0 {
+            Segment::insert(conn, timer.id, self.start, self.end)?;
         }
-    }
 
-    pub fn insert_and_get_id(&self, conn: &Connection) -> AppResult<i32> {
-        &self.insert(&conn)?;
-        let timer = Timer::find_by(&conn, "rid", &self.rid)?;
         Ok(timer.id)
     }
 
@@ -260,3 +416,14 @@ impl CreateTimer {
         .map_err(|e| AppError::from(e))
     }
 }
+
+impl Default for CreateTimer {
+    fn default() -> Self {
+        CreateTimer {
+            rid:   rand_string(12),
+            start: Utc::now(),
+            end:   None,
+            note:  None,
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::env;
+
+use chrono::{DateTime, Utc};
+use clap::ArgMatches;
+use faramir::{
+    errors::{AppError, AppResult},
+    models::timer::{Timer, Timers},
+    Store,
+};
+use serde::{Deserialize, Serialize};
+use termcolor::ColorChoice;
+
+/// How commands print their results, picked with the global `--output`.
+/// Text output is colored according to `--color`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    Text(ColorChoice),
+    Json,
+    Jsonl,
+}
+
+impl Output {
+    pub fn from_matches(matches: &ArgMatches) -> AppResult<Self> {
+        match matches.value_of("output") {
+            None | Some("text") => {
+                Ok(Output::Text(color_choice(matches.value_of("color"))?))
+            },
+            Some("json") => Ok(Output::Json),
+            Some("jsonl") => Ok(Output::Jsonl),
+            Some(output) => Err(AppError::from_str(&format!(
+                "Unknown output format {}. Use text, json or jsonl.",
+                output
+            ))),
+        }
+    }
+
+    pub fn is_text(self) -> bool {
+        matches!(self, Output::Text(_))
+    }
+
+    /// Whether to color text output. Always `Never` for JSON.
+    pub fn color(self) -> ColorChoice {
+        match self {
+            Output::Text(color) => color,
+            _ => ColorChoice::Never,
+        }
+    }
+
+    /// Prints a single document: pretty-printed for `json`, on one line for
+    /// `jsonl`.
+    pub fn document<T: Serialize>(self, value: &T) -> AppResult<()> {
+        match self {
+            Output::Jsonl => println!("{}", serde_json::to_string(value)?),
+            _ => println!("{}", serde_json::to_string_pretty(value)?),
+        }
+
+        Ok(())
+    }
+
+    /// Prints a list: as one array for `json`, one item per line for `jsonl`.
+    pub fn list<T: Serialize>(self, items: &[T]) -> AppResult<()> {
+        match self {
+            Output::Jsonl => {
+                for item in items {
+                    println!("{}", serde_json::to_string(item)?);
+                }
+            },
+            _ => println!("{}", serde_json::to_string_pretty(items)?),
+        }
+
+        Ok(())
+    }
+
+    /// Tells the user why nothing happened. Scripts get an error instead, so
+    /// they don't have to parse prose.
+    pub fn notice(self, message: &str) -> AppResult<()> {
+        match self {
+            Output::Text(_) => {
+                println!("{}", message);
+                Ok(())
+            },
+            _ => Err(AppError::from_str(message)),
+        }
+    }
+}
+
+/// `--color auto` colors output unless `NO_COLOR` is set or stdout isn't a
+/// terminal. `always` and `never` override both.
+fn color_choice(color: Option<&str>) -> AppResult<ColorChoice> {
+    match color {
+        None | Some("auto") => {
+            if env::var_os("NO_COLOR").is_some()
+                || !atty::is(atty::Stream::Stdout)
+            {
+                Ok(ColorChoice::Never)
+            } else {
+                Ok(ColorChoice::Auto)
+            }
+        },
+        Some("always") => Ok(ColorChoice::Always),
+        Some("never") => Ok(ColorChoice::Never),
+        Some(color) => Err(AppError::from_str(&format!(
+            "Unknown color setting {}. Use auto, always or never.",
+            color
+        ))),
+    }
+}
+
+/// A timer with its project, tags and net duration in seconds, as it's
+/// printed by `--output json`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TimerOutput {
+    #[serde(flatten)]
+    pub timer:        Timer,
+    pub project:      Option<String>,
+    pub tags:         Vec<String>,
+    pub duration:     i64,
+    pub paused_since: Option<DateTime<Utc>>,
+}
+
+impl TimerOutput {
+    pub fn load(store: &Store, timer: Timer) -> AppResult<Self> {
+        let segments = store.timer_segments(&timer)?;
+        Ok(TimerOutput {
+            project:      store.timer_project(&timer).ok().map(|p| p.name),
+            tags:         store.timer_tags(&timer)?.names(),
+            duration:     timer.duration_of(Some(&segments)).num_seconds(),
+            paused_since: timer.paused_since_of(Some(&segments)),
+            timer,
+        })
+    }
+
+    /// Loads the projects, tags and segments of every timer at once.
+    pub fn load_all(store: &Store, timers: Timers) -> AppResult<Vec<Self>> {
+        let mut project_names = store.project_names_by_timer()?;
+        let mut tag_names = store.tag_names_by_timer()?;
+        let segments = store.segments_by_timer()?;
+
+        Ok(timers
+            .0
+            .into_iter()
+            .map(|timer| {
+                let segments = segments.get(&timer.id);
+                TimerOutput {
+                    project:      project_names.remove(&timer.id),
+                    tags:         tag_names
+                        .remove(&timer.id)
+                        .unwrap_or_default(),
+                    duration:     timer.duration_of(segments).num_seconds(),
+                    paused_since: timer.paused_since_of(segments),
+                    timer,
+                }
+            })
+            .collect())
+    }
+}
+
+/// A started timer and the ones stopped to make way for it.
+#[derive(Deserialize, Serialize)]
+pub struct StartOutput {
+    pub started: TimerOutput,
+    pub stopped: Vec<TimerOutput>,
+}
+
+/// What `rm` removed.
+#[derive(Serialize)]
+pub struct Removed {
+    #[serde(rename = "type")]
+    pub kind:   &'static str,
+    /// The project or tag name, or the timer's rid.
+    pub name:   String,
+    /// How many timers were removed with a project, or untagged with a tag.
+    pub timers: usize,
+}
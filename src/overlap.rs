@@ -0,0 +1,166 @@
+use std::io;
+
+use chrono::{DateTime, Utc};
+use faramir::{
+    errors::{AppError, AppResult, ErrorKind},
+    models::{
+        config::Config,
+        timer::{Timer, Timers},
+    },
+    Store,
+};
+
+// Shifting stops after this many rounds, in case every new position collides
+// with yet another timer.
+const MAX_SHIFTS: usize = 100;
+
+/// Saves the interval `[start, end)` with `save`, which also gets whether to
+/// allow overlaps, and fails with `ErrorKind::Overlap` when it isn't.
+///
+/// Overlaps are listed on stderr, and the user can trim the existing timers,
+/// shift the new interval past them, proceed anyway, or abort. Shifting
+/// ignores the `exempt` timers, e.g. the running ones `--switch` stops.
+/// Without a terminal to ask on, the overlap is an error.
+pub fn save<T, F>(
+    store: &mut Store, config: &Config, mut start: DateTime<Utc>,
+    mut end: Option<DateTime<Utc>>, exempt: &[i32], allow_overlap: bool,
+    mut save: F,
+) -> AppResult<T>
+where
+    F: FnMut(
+        &mut Store,
+        DateTime<Utc>,
+        Option<DateTime<Utc>>,
+        bool,
+    ) -> AppResult<T>,
+{
+    let overlapping = match save(store, start, end, allow_overlap) {
+        Err(err) => match err.into_kind() {
+            ErrorKind::Overlap(timers) => timers,
+            kind => return Err(AppError::new(kind)),
+        },
+        saved => return saved,
+    };
+
+    if !atty::is(atty::Stream::Stdin) {
+        return Err(refusal(config, &overlapping));
+    }
+
+    eprintln!(
+        "This timer overlaps {} existing timer(s):",
+        overlapping.len()
+    );
+    for timer in &overlapping.0 {
+        eprintln!("  {}", describe(timer, config));
+    }
+    eprintln!(
+        "[t]rim the existing timer(s), [s]hift this timer, [p]roceed \
+         anyway, or [a]bort? (Pass --allow-overlap to skip this check.)"
+    );
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+
+    match input.trim() {
+        // Trimming and saving go together, so a failed save doesn't leave the
+        // other timers trimmed for nothing.
+        "t" | "trim" => {
+            let (trimmed, saved) = store.atomically(|store| {
+                let trimmed = store.trim(overlapping, start, end)?;
+                Ok((trimmed, save(store, start, end, false)?))
+            })?;
+            for timer in trimmed.0 {
+                eprintln!("Trimmed timer {}.", timer.rid);
+            }
+            return Ok(saved);
+        },
+        "s" | "shift" => {
+            shift(store, &mut start, &mut end, exempt)?;
+            eprintln!(
+                "Shifted this timer to start at {}.",
+                config.format_time(&start, false)
+            );
+        },
+        "p" | "proceed" => return save(store, start, end, true),
+        _ => {
+            return Err(AppError::from_str(
+                "Aborted because of overlapping timers.",
+            ))
+        },
+    }
+
+    save(store, start, end, false)
+}
+
+/// Spells out an overlap error with the overlapping timers' times, for when
+/// there's no one to ask about it. Other errors are returned as they are.
+pub fn explain(config: &Config, err: AppError) -> AppError {
+    match err.into_kind() {
+        ErrorKind::Overlap(timers) => refusal(config, &timers),
+        kind => AppError::new(kind),
+    }
+}
+
+fn refusal(config: &Config, overlapping: &Timers) -> AppError {
+    let timers: Vec<String> = overlapping
+        .0
+        .iter()
+        .map(|timer| describe(timer, config))
+        .collect();
+    AppError::from_str(&format!(
+        "This timer overlaps {} existing timer(s): {}. Pass --allow-overlap \
+         to save it anyway.",
+        timers.len(),
+        timers.join(", ")
+    ))
+}
+
+pub fn describe(timer: &Timer, config: &Config) -> String {
+    let end = match &timer.end {
+        Some(end) => config.format_time(end, false),
+        None => "running".into(),
+    };
+
+    format!(
+        "{} ({} - {})",
+        timer.rid,
+        config.format_time(&timer.start, false),
+        end
+    )
+}
+
+/// Moves `[start, end)` later, keeping its length, until it no longer
+/// overlaps anything but the `exempt` timers.
+fn shift(
+    store: &Store, start: &mut DateTime<Utc>, end: &mut Option<DateTime<Utc>>,
+    exempt: &[i32],
+) -> AppResult<()> {
+    for _ in 0..MAX_SHIFTS {
+        let mut overlapping = store.overlapping(*start, *end)?;
+        overlapping.0.retain(|timer| !exempt.contains(&timer.id));
+        if overlapping.is_empty() {
+            return Ok(());
+        }
+
+        let mut latest_end = *start;
+        for timer in &overlapping.0 {
+            match timer.end {
+                Some(timer_end) if timer_end > latest_end => {
+                    latest_end = timer_end
+                },
+                Some(_) => {},
+                None => {
+                    return Err(AppError::from_str(&format!(
+                        "This timer can't be shifted past running timer {}.",
+                        timer.rid
+                    )))
+                },
+            }
+        }
+
+        *end = end.map(|end| latest_end + (end - *start));
+        *start = latest_end;
+    }
+
+    Err(AppError::from_str("Unable to find a free slot for this timer."))
+}
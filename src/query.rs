@@ -0,0 +1,207 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{types::ToSql, Connection, Row};
+
+use crate::{
+    errors::{AppError, AppResult},
+    models::timer::{Timer, Timers},
+};
+
+/// A model that can be read from a query's row, e.g. one selected with
+/// `SELECT * FROM timers`.
+pub trait FromRow: Sized {
+    /// How many columns the model takes up, so joined rows can be split.
+    const COLUMNS: usize;
+
+    /// Reads the model from the row's columns, starting at `offset`.
+    fn from_row(row: &Row, offset: usize) -> rusqlite::Result<Self>;
+}
+
+/// Two models selected side by side, like `SELECT a.*, b.*`.
+impl<A: FromRow, B: FromRow> FromRow for (A, B) {
+    const COLUMNS: usize = A::COLUMNS + B::COLUMNS;
+
+    fn from_row(row: &Row, offset: usize) -> rusqlite::Result<Self> {
+        Ok((
+            A::from_row(row, offset)?,
+            B::from_row(row, offset + A::COLUMNS)?,
+        ))
+    }
+}
+
+/// Runs `sql` with `params` bound, reading every row as a `T`.
+pub fn all<T, P>(conn: &Connection, sql: &str, params: P) -> AppResult<Vec<T>>
+where
+    T: FromRow,
+    P: IntoIterator,
+    P::Item: ToSql,
+{
+    let mut stmt = conn.prepare(sql)?;
+    let row_iter = stmt.query_map(params, |row| T::from_row(row, 0))?;
+
+    let mut items = vec![];
+    for item in row_iter {
+        items.push(item?);
+    }
+
+    Ok(items)
+}
+
+/// Runs `sql` with `params` bound, reading the first row as a `T`.
+pub fn one<T, P>(conn: &Connection, sql: &str, params: P) -> AppResult<T>
+where
+    T: FromRow,
+    P: IntoIterator,
+    P::Item: ToSql,
+{
+    let mut stmt = conn.prepare(sql)?;
+    stmt.query_row(params, |row| T::from_row(row, 0))
+        .map_err(AppError::from)
+}
+
+/// `count` comma-separated `?`s, for binding a list in `IN (...)`.
+pub fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+/// The columns timers can be looked up and sorted by.
+#[derive(Clone, Copy, Debug)]
+pub enum TimerColumn {
+    Id,
+    Rid,
+    Start,
+}
+
+impl TimerColumn {
+    fn name(self) -> &'static str {
+        match self {
+            TimerColumn::Id => "t.id",
+            TimerColumn::Rid => "t.rid",
+            TimerColumn::Start => "t.start",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+/// A `SELECT` of timers. Conditions only name columns from `TimerColumn`,
+/// and every value is bound as a parameter.
+#[derive(Default)]
+pub struct TimerQuery {
+    conditions: Vec<String>,
+    values:     Vec<Box<dyn ToSql>>,
+    order:      Option<(TimerColumn, Order)>,
+    limit:      Option<i64>,
+}
+
+impl TimerQuery {
+    pub fn new() -> Self {
+        TimerQuery::default()
+    }
+
+    /// Only running timers, or only finished ones.
+    pub fn running(mut self, running: bool) -> Self {
+        self.conditions.push(match running {
+            true => "t.end IS NULL".into(),
+            false => "t.end IS NOT NULL".into(),
+        });
+        self
+    }
+
+    pub fn eq<V: ToSql + 'static>(
+        mut self, column: TimerColumn, value: V,
+    ) -> Self {
+        self.conditions.push(format!("{} = ?", column.name()));
+        self.values.push(Box::new(value));
+        self
+    }
+
+    /// Only timers started at `from` or later.
+    pub fn started_from(mut self, from: DateTime<Utc>) -> Self {
+        self.conditions.push("t.start >= ?".into());
+        self.values.push(Box::new(from));
+        self
+    }
+
+    /// Only timers started before `to`.
+    pub fn started_before(mut self, to: DateTime<Utc>) -> Self {
+        self.conditions.push("t.start < ?".into());
+        self.values.push(Box::new(to));
+        self
+    }
+
+    /// Only timers in one of these projects.
+    pub fn projects(mut self, names: &[String]) -> Self {
+        self.conditions.push(format!(
+            "t.id IN (SELECT pt.timer_id FROM projects_timers pt JOIN \
+             projects p ON p.id = pt.project_id WHERE p.name IN ({}))",
+            placeholders(names.len())
+        ));
+        for name in names {
+            self.values.push(Box::new(name.clone()));
+        }
+        self
+    }
+
+    /// Only timers with at least one of these tags.
+    pub fn tags(mut self, names: &[String]) -> Self {
+        self.conditions.push(format!(
+            "t.id IN (SELECT tt.timer_id FROM tags_timers tt JOIN tags ta ON \
+             ta.id = tt.tag_id WHERE ta.name IN ({}))",
+            placeholders(names.len())
+        ));
+        for name in names {
+            self.values.push(Box::new(name.clone()));
+        }
+        self
+    }
+
+    pub fn order_by(mut self, column: TimerColumn, order: Order) -> Self {
+        self.order = Some((column, order));
+        self
+    }
+
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn fetch(self, conn: &Connection) -> AppResult<Timers> {
+        let (sql, values) = self.build();
+        all(conn, &sql, values).map(Timers::new)
+    }
+
+    /// The first matching timer, or an error if there isn't one.
+    pub fn fetch_one(self, conn: &Connection) -> AppResult<Timer> {
+        let (sql, values) = self.build();
+        one(conn, &sql, values)
+    }
+
+    fn build(self) -> (String, Vec<Box<dyn ToSql>>) {
+        let mut sql = "SELECT t.* FROM timers t".to_string();
+
+        if !self.conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&self.conditions.join(" AND "));
+        }
+
+        let mut values = self.values;
+        if let Some((column, order)) = self.order {
+            let order = match order {
+                Order::Asc => "ASC",
+                Order::Desc => "DESC",
+            };
+            sql.push_str(&format!(" ORDER BY {} {}", column.name(), order));
+        }
+
+        if let Some(limit) = self.limit {
+            sql.push_str(" LIMIT ?");
+            values.push(Box::new(limit));
+        }
+
+        (sql, values)
+    }
+}
@@ -0,0 +1,164 @@
+use std::{cmp::Ordering, collections::HashMap, str::FromStr};
+
+use chrono::{offset::TimeZone, Datelike, Duration};
+use chrono_tz::Tz;
+use rusqlite::Connection;
+use serde::Serialize;
+
+use crate::{
+    errors::{AppError, AppResult},
+    filter::TimerFilter,
+    models::{
+        config::Config,
+        project::Projects,
+        segment::Segments,
+        tag::Tags,
+        timer::{Timer, Timers},
+    },
+};
+
+const NO_TAGS: &str = "(no tags)";
+const NO_PROJECT: &str = "(no project)";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GroupBy {
+    Project,
+    Tag,
+    Day,
+    Week,
+}
+
+impl FromStr for GroupBy {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> AppResult<Self> {
+        match s {
+            "project" => Ok(GroupBy::Project),
+            "tag" => Ok(GroupBy::Tag),
+            "day" => Ok(GroupBy::Day),
+            "week" => Ok(GroupBy::Week),
+            _ => Err(AppError::from_str(
+                "--group-by must be one of project, tag, day, week.",
+            )),
+        }
+    }
+}
+
+impl GroupBy {
+    fn is_chronological(self) -> bool {
+        self == GroupBy::Day || self == GroupBy::Week
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReportGroup {
+    pub name:      String,
+    pub seconds:   i64,
+    pub subgroups: Vec<ReportGroup>,
+}
+
+/// Time aggregated over a set of timers.
+///
+/// Grouping by project nests tags underneath; every other grouping nests
+/// projects. A timer with several tags counts towards each of them, so tag
+/// percentages can add up to more than 100%.
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub groups:        Vec<ReportGroup>,
+    pub total_seconds: i64,
+    pub timer_count:   usize,
+}
+
+impl Report {
+    pub fn build(
+        conn: &Connection, config: &Config, filter: &TimerFilter,
+        group_by: GroupBy,
+    ) -> AppResult<Self> {
+        let tz = config.tz()?;
+        let timers = Timers::filter(conn, filter)?;
+        let project_names = Projects::names_by_timer(conn)?;
+        let tag_names = Tags::names_by_timer(conn)?;
+        let segments = Segments::by_timer(conn)?;
+        let no_tags = vec![NO_TAGS.to_string()];
+
+        let mut totals: HashMap<String, i64> = HashMap::new();
+        let mut groups: HashMap<String, HashMap<String, i64>> = HashMap::new();
+        for timer in &timers.0 {
+            let seconds =
+                timer.duration_of(segments.get(&timer.id)).num_seconds();
+            let project = match project_names.get(&timer.id) {
+                Some(name) => name.clone(),
+                None => NO_PROJECT.into(),
+            };
+            let tags = tag_names.get(&timer.id).unwrap_or(&no_tags);
+
+            let (keys, subkeys) = match group_by {
+                GroupBy::Project => (vec![project], tags.clone()),
+                GroupBy::Tag => (tags.clone(), vec![project]),
+                GroupBy::Day => (vec![day_key(&tz, timer)], vec![project]),
+                GroupBy::Week => (vec![week_key(&tz, timer)], vec![project]),
+            };
+
+            // A multi-tagged timer shows up in several subgroups, so group
+            // totals are counted per timer rather than summed from them.
+            for key in keys {
+                *totals.entry(key.clone()).or_default() += seconds;
+                let subgroups = groups.entry(key).or_default();
+                for subkey in &subkeys {
+                    *subgroups.entry(subkey.clone()).or_default() += seconds;
+                }
+            }
+        }
+
+        let mut groups = groups
+            .into_iter()
+            .map(|(name, subgroups)| {
+                let mut subgroups = subgroups
+                    .into_iter()
+                    .map(|(name, seconds)| ReportGroup {
+                        name,
+                        seconds,
+                        subgroups: vec![],
+                    })
+                    .collect::<Vec<ReportGroup>>();
+                subgroups.sort_by(by_seconds);
+
+                ReportGroup {
+                    seconds: totals[&name],
+                    name,
+                    subgroups,
+                }
+            })
+            .collect::<Vec<ReportGroup>>();
+
+        if group_by.is_chronological() {
+            groups.sort_by(|a, b| a.name.cmp(&b.name));
+        } else {
+            groups.sort_by(by_seconds);
+        }
+
+        Ok(Report {
+            groups,
+            total_seconds: timers.total_seconds(&segments),
+            timer_count: timers.len(),
+        })
+    }
+}
+
+fn by_seconds(a: &ReportGroup, b: &ReportGroup) -> Ordering {
+    b.seconds.cmp(&a.seconds).then_with(|| a.name.cmp(&b.name))
+}
+
+fn day_key(tz: &Tz, timer: &Timer) -> String {
+    tz.from_utc_datetime(&timer.start.naive_utc())
+        .format("%Y-%m-%d")
+        .to_string()
+}
+
+fn week_key(tz: &Tz, timer: &Timer) -> String {
+    let date = tz.from_utc_datetime(&timer.start.naive_utc()).date();
+    let monday =
+        date - Duration::days(date.weekday().num_days_from_monday() as i64);
+
+    format!("week of {}", monday.format("%Y-%m-%d"))
+}
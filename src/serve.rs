@@ -0,0 +1,578 @@
+use chrono::{DateTime, Utc};
+use faramir::{
+    errors::{AppError, AppResult, ErrorKind},
+    filter::TimerFilter,
+    models::{
+        config::Config,
+        timer::{CreateTimer, Timers},
+    },
+    report::GroupBy,
+    timeparse, StartOptions, Store,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::output::{Removed, StartOutput, TimerOutput};
+
+pub const DEFAULT_BIND: &str = "127.0.0.1:7777";
+
+type ApiResult = Result<(u16, Value), ApiError>;
+
+/// An error response: `{"error": {"kind": ..., "message": ...}}` with a
+/// matching status code.
+#[derive(Debug)]
+struct ApiError {
+    status:  u16,
+    kind:    &'static str,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, kind: &'static str, message: String) -> Self {
+        ApiError {
+            status,
+            kind,
+            message,
+        }
+    }
+
+    fn invalid(message: &str) -> Self {
+        ApiError::new(400, "invalid", message.into())
+    }
+
+    fn conflict(message: String) -> Self {
+        ApiError::new(409, "conflict", message)
+    }
+
+    fn overlap(overlapping: &Timers) -> Self {
+        let rids: Vec<&str> =
+            overlapping.0.iter().map(|t| t.rid.as_str()).collect();
+        ApiError::new(
+            409,
+            "overlap",
+            format!(
+                "This timer overlaps {} existing timer(s): {}. Set \
+                 allow_overlap to save it anyway.",
+                rids.len(),
+                rids.join(", ")
+            ),
+        )
+    }
+
+    fn body(&self) -> Value {
+        json!({
+            "error": {
+                "kind": self.kind,
+                "message": self.message,
+            }
+        })
+    }
+}
+
+impl From<AppError> for ApiError {
+    fn from(err: AppError) -> Self {
+        let (status, kind) = match err.kind() {
+            ErrorKind::Overlap(overlapping) => {
+                return ApiError::overlap(overlapping)
+            },
+            ErrorKind::Generic(_) | ErrorKind::StringParse(_) => {
+                (400, "invalid")
+            },
+            ErrorKind::ChronoParse(_) => (400, "invalid_time"),
+            ErrorKind::SerdeJson(_) => (400, "invalid_json"),
+            ErrorKind::NotFound(_)
+            | ErrorKind::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => {
+                (404, "not_found")
+            },
+            ErrorKind::Rusqlite(_) => (500, "database"),
+            ErrorKind::IO(_) | ErrorKind::Csv(_) => (500, "internal"),
+        };
+
+        ApiError::new(status, kind, err.to_string())
+    }
+}
+
+/// Serves `store` as JSON over HTTP until the process is stopped.
+///
+/// Requests are handled one at a time on the store's single connection, so
+/// each one sees the previous one's writes. Other faramir commands can keep
+/// using the database meanwhile.
+pub fn serve(store: &mut Store, config: &Config, bind: &str) -> AppResult<()> {
+    let server = Server::http(bind).map_err(|err| {
+        AppError::from_str(&format!("Unable to listen on {}: {}", bind, err))
+    })?;
+    println!("Listening on http://{}", bind);
+
+    for mut request in server.incoming_requests() {
+        let (status, body) = match route(store, config, &mut request) {
+            Ok(reply) => reply,
+            Err(err) => (err.status, err.body()),
+        };
+        println!("{} {} {}", request.method(), request.url(), status);
+
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(
+                Header::from_bytes(
+                    &b"Content-Type"[..],
+                    &b"application/json"[..],
+                )
+                .unwrap(),
+            );
+        if let Err(err) = request.respond(response) {
+            eprintln!("Unable to send the response: {}", err);
+        }
+    }
+
+    Ok(())
+}
+
+fn route(
+    store: &mut Store, config: &Config, request: &mut Request,
+) -> ApiResult {
+    let url = request.url().to_string();
+    let (path, query) = match url.find('?') {
+        Some(i) => (&url[..i], Query::parse(&url[i + 1..])),
+        None => (url.as_str(), Query::default()),
+    };
+    let segments: Vec<String> = path
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(|s| decode(s, false))
+        .collect();
+    let segments: Vec<&str> = segments.iter().map(|s| s.as_str()).collect();
+
+    match (request.method(), segments.as_slice()) {
+        (Method::Get, ["status"]) => {
+            ok(&TimerOutput::load_all(store, store.running()?)?)
+        },
+        (Method::Get, ["timers"]) => {
+            let filter = timer_filter(config, &query)?;
+            ok(&TimerOutput::load_all(
+                store,
+                store.timers(&filter)?,
+            )?)
+        },
+        (Method::Post, ["timers"]) => add_timer(store, config, body(request)?),
+        (Method::Post, ["timers", "start"]) => {
+            start_timer(store, config, body(request)?)
+        },
+        (Method::Get, ["timers", rid]) => {
+            ok(&TimerOutput::load(store, store.timer(rid)?)?)
+        },
+        (Method::Patch, ["timers", rid]) => {
+            edit_timer(store, config, rid, body(request)?)
+        },
+        (Method::Delete, ["timers", rid]) => {
+            let timer = store.delete_timer(rid)?;
+            ok(&Removed {
+                kind:   "timer",
+                name:   timer.rid,
+                timers: 1,
+            })
+        },
+        (Method::Post, ["timers", rid, action]) => {
+            timer_action(store, config, rid, action, body(request)?)
+        },
+        (Method::Get, ["projects"]) => ok(&store.projects()?.0),
+        (Method::Get, ["projects", name]) => ok(&store.project(name)?),
+        (Method::Patch, ["projects", name]) => {
+            edit_project(store, name, body(request)?)
+        },
+        (Method::Delete, ["projects", name]) => {
+            let timers = store.delete_project(name)?;
+            ok(&Removed {
+                kind: "project",
+                name: name.to_string(),
+                timers,
+            })
+        },
+        (Method::Get, ["tags"]) => ok(&store.tags()?.0),
+        (Method::Get, ["tags", name]) => ok(&store.tag(name)?),
+        (Method::Patch, ["tags", name]) => {
+            edit_tag(store, name, body(request)?)
+        },
+        (Method::Delete, ["tags", name]) => {
+            let timers = store.delete_tag(name)?;
+            ok(&Removed {
+                kind: "tag",
+                name: name.to_string(),
+                timers,
+            })
+        },
+        (Method::Get, ["report"]) => {
+            let mut filter = timer_filter(config, &query)?;
+            filter.limit = None;
+            let group_by = match query.get("group_by") {
+                Some(group_by) => group_by.parse::<GroupBy>()?,
+                None => GroupBy::Project,
+            };
+            ok(&store.report(config, &filter, group_by)?)
+        },
+        (method, _) => Err(ApiError::new(
+            404,
+            "not_found",
+            format!("No route for {} {}.", method, path),
+        )),
+    }
+}
+
+fn ok<T: Serialize>(value: &T) -> ApiResult {
+    Ok((200, serde_json::to_value(value).map_err(AppError::from)?))
+}
+
+fn created<T: Serialize>(value: &T) -> ApiResult {
+    Ok((201, serde_json::to_value(value).map_err(AppError::from)?))
+}
+
+/// Reads the request body as JSON. An empty body counts as `{}`.
+fn body<T: DeserializeOwned>(request: &mut Request) -> Result<T, ApiError> {
+    let mut body = String::new();
+    request
+        .as_reader()
+        .read_to_string(&mut body)
+        .map_err(AppError::from)?;
+    if body.trim().is_empty() {
+        body = "{}".into();
+    }
+
+    serde_json::from_str(&body).map_err(|err| {
+        ApiError::new(
+            400,
+            "invalid_json",
+            format!("Invalid request body: {}", err),
+        )
+    })
+}
+
+/// Parses an optional time the way the CLI's `--at` does, defaulting to now.
+fn parse_at(
+    config: &Config, at: Option<&str>,
+) -> Result<DateTime<Utc>, ApiError> {
+    match at {
+        Some(at) => Ok(timeparse::parse_time(at, config)?),
+        None => Ok(Utc::now()),
+    }
+}
+
+/// The `from`, `to`, `project`, `tag`, `include_running` and `limit` query
+/// parameters. `project` and `tag` can be repeated.
+fn timer_filter(
+    config: &Config, query: &Query,
+) -> Result<TimerFilter, ApiError> {
+    let mut filter = TimerFilter::default();
+
+    if let Some(from) = query.get("from") {
+        filter.from = Some(timeparse::parse_time(from, config)?);
+    }
+    if let Some(to) = query.get("to") {
+        filter.to = Some(timeparse::parse_time(to, config)?);
+    }
+    filter.projects = query.all("project");
+    filter.tags = query.all("tag");
+
+    filter.include_running = match query.get("include_running") {
+        None | Some("false") => false,
+        Some("true") => true,
+        Some(_) => {
+            return Err(ApiError::invalid(
+                "include_running must be true or false.",
+            ))
+        },
+    };
+    if let Some(limit) = query.get("limit") {
+        filter.limit = Some(
+            limit
+                .parse()
+                .map_err(|_| ApiError::invalid("limit must be a number."))?,
+        );
+    }
+
+    Ok(filter)
+}
+
+#[derive(Deserialize)]
+struct StartRequest {
+    project:       String,
+    #[serde(default)]
+    tags:          Vec<String>,
+    note:          Option<String>,
+    at:            Option<String>,
+    #[serde(default)]
+    switch:        bool,
+    #[serde(default)]
+    allow_overlap: bool,
+}
+
+fn start_timer(
+    store: &mut Store, config: &Config, req: StartRequest,
+) -> ApiResult {
+    let start = parse_at(config, req.at.as_deref())?;
+    let switch = config.single_active || req.switch;
+    let (started, stopped) = store.start(
+        &req.project,
+        &req.tags,
+        req.note,
+        start,
+        StartOptions {
+            switch,
+            allow_overlap: req.allow_overlap,
+        },
+    )?;
+
+    created(&StartOutput {
+        started: TimerOutput::load(store, started)?,
+        stopped: TimerOutput::load_all(store, stopped)?,
+    })
+}
+
+#[derive(Deserialize)]
+struct AddRequest {
+    project:       String,
+    #[serde(default)]
+    tags:          Vec<String>,
+    note:          Option<String>,
+    start:         String,
+    end:           String,
+    #[serde(default)]
+    allow_overlap: bool,
+}
+
+fn add_timer(store: &mut Store, config: &Config, req: AddRequest) -> ApiResult {
+    let start = timeparse::parse_time(&req.start, config)?;
+    let end = timeparse::parse_time(&req.end, config)?;
+
+    let create_timer = CreateTimer::new(start, Some(end), req.note);
+    let timer =
+        store.add(&req.project, &req.tags, &create_timer, req.allow_overlap)?;
+    created(&TimerOutput::load(store, timer)?)
+}
+
+/// Fields left out stay as they are. An empty `note` removes the note.
+#[derive(Deserialize)]
+struct TimerPatch {
+    start:         Option<String>,
+    end:           Option<String>,
+    note:          Option<String>,
+    project:       Option<String>,
+    tags:          Option<Vec<String>>,
+    #[serde(default)]
+    allow_overlap: bool,
+}
+
+fn edit_timer(
+    store: &mut Store, config: &Config, rid: &str, patch: TimerPatch,
+) -> ApiResult {
+    let mut edit = store.edit_timer(rid)?;
+
+    if let Some(start) = patch.start {
+        edit.timer.start = timeparse::parse_time(&start, config)?;
+    }
+    if let Some(end) = patch.end {
+        edit.timer.end = Some(timeparse::parse_time(&end, config)?);
+    }
+    if let Some(note) = patch.note {
+        edit.timer.note = Some(note).filter(|n| !n.is_empty());
+    }
+    if let Some(project) = patch.project {
+        if project.is_empty() {
+            return Err(ApiError::invalid("A timer must have a project."));
+        }
+        edit.project = project;
+    }
+    if let Some(tags) = patch.tags {
+        edit.tags = tags;
+    }
+
+    store.update_timer(
+        &edit.timer,
+        &edit.project,
+        &edit.tags,
+        patch.allow_overlap,
+    )?;
+    ok(&TimerOutput::load(store, store.timer(rid)?)?)
+}
+
+#[derive(Default, Deserialize)]
+struct AtRequest {
+    at: Option<String>,
+}
+
+/// `stop`, `pause` or `resume` a timer at `at`, or now.
+fn timer_action(
+    store: &mut Store, config: &Config, rid: &str, action: &str, req: AtRequest,
+) -> ApiResult {
+    let mut timer = store.timer(rid)?;
+    let at = parse_at(config, req.at.as_deref())?;
+
+    match action {
+        "stop" => {
+            if timer.end.is_some() {
+                return Err(ApiError::conflict(format!(
+                    "Timer {} isn't running.",
+                    timer.rid
+                )));
+            }
+            store.stop(&mut timer, at)?;
+        },
+        "pause" => store.pause(&timer, at)?,
+        "resume" => store.resume(&timer, at)?,
+        _ => {
+            return Err(ApiError::new(
+                404,
+                "not_found",
+                format!("Unknown timer action {}.", action),
+            ))
+        },
+    }
+
+    ok(&TimerOutput::load(store, store.timer(rid)?)?)
+}
+
+/// Fields left out stay as they are. An empty `description` or `color`
+/// removes it.
+#[derive(Deserialize)]
+struct ProjectPatch {
+    name:        Option<String>,
+    description: Option<String>,
+    color:       Option<String>,
+    archived:    Option<bool>,
+    hourly_rate: Option<f64>,
+}
+
+fn edit_project(store: &Store, name: &str, patch: ProjectPatch) -> ApiResult {
+    let mut project = store.project(name)?;
+
+    if let Some(new_name) = patch.name {
+        if new_name.is_empty() {
+            return Err(ApiError::invalid("A project must have a name."));
+        }
+        if new_name != project.name && store.project(&new_name).is_ok() {
+            return Err(ApiError::conflict(format!(
+                "A project named {} already exists.",
+                new_name
+            )));
+        }
+        project.name = new_name;
+    }
+    if let Some(description) = patch.description {
+        project.description = Some(description).filter(|d| !d.is_empty());
+    }
+    if let Some(color) = patch.color {
+        project.color = Some(color).filter(|c| !c.is_empty());
+    }
+    if let Some(archived) = patch.archived {
+        project.archived = archived;
+    }
+    if let Some(hourly_rate) = patch.hourly_rate {
+        project.hourly_rate = Some(hourly_rate);
+    }
+
+    store.update_project(&project)?;
+    ok(&project)
+}
+
+/// Fields left out stay as they are. An empty `description` or `color`
+/// removes it.
+#[derive(Deserialize)]
+struct TagPatch {
+    name:        Option<String>,
+    description: Option<String>,
+    color:       Option<String>,
+}
+
+fn edit_tag(store: &Store, name: &str, patch: TagPatch) -> ApiResult {
+    let mut tag = store.tag(name)?;
+
+    if let Some(new_name) = patch.name {
+        if new_name.is_empty() || new_name.contains(',') {
+            return Err(ApiError::invalid(
+                "A tag name must be non-empty and can't contain commas.",
+            ));
+        }
+        if new_name != tag.name && store.tag(&new_name).is_ok() {
+            return Err(ApiError::conflict(format!(
+                "A tag named {} already exists.",
+                new_name
+            )));
+        }
+        tag.name = new_name;
+    }
+    if let Some(description) = patch.description {
+        tag.description = Some(description).filter(|d| !d.is_empty());
+    }
+    if let Some(color) = patch.color {
+        tag.color = Some(color).filter(|c| !c.is_empty());
+    }
+
+    store.update_tag(&tag)?;
+    ok(&tag)
+}
+
+/// A URL's decoded query parameters, in order.
+#[derive(Default)]
+struct Query(Vec<(String, String)>);
+
+impl Query {
+    fn parse(query: &str) -> Self {
+        Query(
+            query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| match pair.find('=') {
+                    Some(i) => {
+                        (decode(&pair[..i], true), decode(&pair[i + 1..], true))
+                    },
+                    None => (decode(pair, true), String::new()),
+                })
+                .collect(),
+        )
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn all(&self, key: &str) -> Vec<String> {
+        self.0
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+            .collect()
+    }
+}
+
+/// Decodes `%XX` escapes, and `+` as a space when `plus_is_space` is set.
+fn decode(s: &str, plus_is_space: bool) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes[i] {
+            b'%' if i + 2 < bytes.len() => {
+                std::str::from_utf8(&bytes[i + 1..i + 3])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            },
+            _ => None,
+        };
+
+        match (escaped, bytes[i]) {
+            (Some(byte), _) => {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            },
+            (None, b'+') if plus_is_space => decoded.push(b' '),
+            (None, byte) => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,170 @@
+use std::str::FromStr;
+
+use faramir::{
+    errors::{AppError, AppResult},
+    models::config::Config,
+};
+use serde_json::json;
+
+use crate::output::TimerOutput;
+
+/// The status bars `status --format` can print for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BarFormat {
+    Waybar,
+    I3blocks,
+    Polybar,
+    Tmux,
+    PlainShort,
+}
+
+impl FromStr for BarFormat {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> AppResult<Self> {
+        match s {
+            "waybar" => Ok(BarFormat::Waybar),
+            "i3blocks" => Ok(BarFormat::I3blocks),
+            "polybar" => Ok(BarFormat::Polybar),
+            "tmux" => Ok(BarFormat::Tmux),
+            "plain-short" => Ok(BarFormat::PlainShort),
+            _ => Err(AppError::from_str(
+                "--format must be one of waybar, i3blocks, polybar, tmux, \
+                 plain-short.",
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Running,
+    Paused,
+    Idle,
+}
+
+impl State {
+    fn of(timers: &[TimerOutput]) -> Self {
+        if timers.is_empty() {
+            State::Idle
+        } else if timers.iter().any(|t| t.paused_since.is_none()) {
+            State::Running
+        } else {
+            State::Paused
+        }
+    }
+
+    /// The CSS class waybar styles the module with.
+    fn class(self) -> &'static str {
+        match self {
+            State::Running => "running",
+            State::Paused => "paused",
+            State::Idle => "idle",
+        }
+    }
+
+    fn color(self) -> Option<&'static str> {
+        match self {
+            State::Running => Some("#00ff00"),
+            State::Paused => Some("#ffff00"),
+            State::Idle => None,
+        }
+    }
+}
+
+/// The running timers as `format` expects them. When idle, the text is
+/// empty, so bars can hide the block.
+///
+/// i3blocks reads three lines (full text, short text and color) each time it
+/// runs the command, but a single JSON object per line from a persistent
+/// one, so `watch` picks between the two.
+pub fn render(
+    format: BarFormat, config: &Config, timers: &[TimerOutput], watch: bool,
+) -> String {
+    let state = State::of(timers);
+    let labels: Vec<String> = timers.iter().map(label).collect();
+    let text = labels.join(" | ");
+    let short = match labels.len() {
+        0 => String::new(),
+        1 => labels[0].clone(),
+        n => format!("{} +{}", labels[0], n - 1),
+    };
+
+    match (format, state.color()) {
+        (BarFormat::Waybar, _) => json!({
+            "text": text,
+            "tooltip": tooltip(config, timers),
+            "class": state.class(),
+            "alt": state.class(),
+        })
+        .to_string(),
+        (BarFormat::I3blocks, color) if watch => json!({
+            "full_text": text,
+            "short_text": short,
+            "color": color,
+        })
+        .to_string(),
+        (BarFormat::I3blocks, Some(color)) => {
+            format!("{}\n{}\n{}", text, short, color)
+        },
+        (BarFormat::I3blocks, None) => format!("{}\n{}", text, short),
+        (BarFormat::Polybar, Some(color)) => {
+            format!("%{{F{}}}{}%{{F-}}", color, text)
+        },
+        (BarFormat::Tmux, Some(color)) => {
+            format!("#[fg={}]{}#[default]", color, text)
+        },
+        (BarFormat::Polybar, None) | (BarFormat::Tmux, None) => text,
+        (BarFormat::PlainShort, _) => short,
+    }
+}
+
+/// E.g. `book 1:05`, or `book 1:05 (paused)`.
+fn label(timer: &TimerOutput) -> String {
+    let mut label = format!("{} {}", project(timer), clock(timer.duration));
+    if timer.paused_since.is_some() {
+        label.push_str(" (paused)");
+    }
+
+    label
+}
+
+fn project(timer: &TimerOutput) -> &str {
+    timer.project.as_deref().unwrap_or("(no project)")
+}
+
+/// One line per timer, with its tags and when it started.
+fn tooltip(config: &Config, timers: &[TimerOutput]) -> String {
+    if timers.is_empty() {
+        return "No timers are running.".into();
+    }
+
+    let lines: Vec<String> = timers
+        .iter()
+        .map(|timer| {
+            let tags = match timer.tags.is_empty() {
+                true => String::new(),
+                false => format!(" [{}]", timer.tags.join(", ")),
+            };
+            let paused = match timer.paused_since {
+                Some(_) => " (paused)",
+                None => "",
+            };
+            format!(
+                "{}{} {}, since {}{}",
+                project(timer),
+                tags,
+                clock(timer.duration),
+                config.format_time(&timer.timer.start, false),
+                paused
+            )
+        })
+        .collect();
+
+    lines.join("\n")
+}
+
+/// Elapsed seconds as hours and minutes, e.g. `1:05`.
+fn clock(seconds: i64) -> String {
+    format!("{}:{:02}", seconds / 3600, seconds % 3600 / 60)
+}
@@ -0,0 +1,413 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use rusqlite::Connection;
+
+use crate::{
+    db,
+    errors::{AppError, AppResult, ErrorKind},
+    export::ExportedTimer,
+    filter::TimerFilter,
+    import::{self, ImportSummary},
+    migrations,
+    models::{
+        config::Config,
+        project::{Project, Projects},
+        segment::Segments,
+        tag::{Tag, Tags},
+        timer::{CreateTimer, EditTimer, Timer, Timers},
+    },
+    query::TimerColumn,
+    report::{GroupBy, Report},
+};
+
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How `Store::start` treats the timers that are already there.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StartOptions {
+    /// Stop every running timer before starting the new one.
+    pub switch:        bool,
+    /// Start the timer even if it overlaps another one.
+    pub allow_overlap: bool,
+}
+
+/// A faramir database. Every method returns what it did instead of printing
+/// it, so callers decide how to show it.
+pub struct Store {
+    conn:    Connection,
+    backups: Vec<PathBuf>,
+}
+
+impl Store {
+    /// Opens the database at `path`, creating it if needed, and brings its
+    /// schema up to date.
+    pub fn open(path: &Path) -> AppResult<Self> {
+        let mut conn = Connection::open(path)?;
+        // `faramir serve` keeps the database open while other commands run,
+        // so wait for its writes to finish instead of failing right away.
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        let backups = migrations::migrate(&mut conn, path)?;
+
+        Ok(Store { conn, backups })
+    }
+
+    /// Where the database was backed up to before `open` ran a destructive
+    /// migration.
+    pub fn backups(&self) -> &[PathBuf] {
+        &self.backups
+    }
+
+    /// Runs `f` as a single transaction, so that either everything it saves
+    /// through the store is kept or, if it fails, none of it is. Calls can be
+    /// nested, and the store's own writes use it too.
+    ///
+    /// It's a savepoint rather than a `rusqlite::Transaction`, since those
+    /// can't be nested, and rusqlite's `Savepoint` stays open when it's
+    /// rolled back.
+    pub fn atomically<T, F>(&mut self, f: F) -> AppResult<T>
+    where
+        F: FnOnce(&mut Store) -> AppResult<T>,
+    {
+        self.conn.execute_batch("SAVEPOINT atomically")?;
+        match f(self) {
+            Ok(value) => {
+                self.conn.execute_batch("RELEASE atomically")?;
+                Ok(value)
+            },
+            Err(err) => {
+                self.conn.execute_batch(
+                    "ROLLBACK TO atomically; RELEASE atomically",
+                )?;
+                Err(err)
+            },
+        }
+    }
+
+    pub fn timer(&self, rid: &str) -> AppResult<Timer> {
+        Timer::find_by(&self.conn, TimerColumn::Rid, rid).map_err(|err| {
+            match err.kind() {
+                ErrorKind::Rusqlite(rusqlite::Error::QueryReturnedNoRows) => {
+                    AppError::not_found(&format!("Timer {} not found.", rid))
+                },
+                _ => err,
+            }
+        })
+    }
+
+    /// The most recently created timer.
+    pub fn last_timer(&self) -> AppResult<Timer> {
+        Timer::last(&self.conn)
+    }
+
+    pub fn running(&self) -> AppResult<Timers> {
+        Timers::currently_running(&self.conn)
+    }
+
+    /// The timers matching `filter`, oldest first.
+    pub fn timers(&self, filter: &TimerFilter) -> AppResult<Timers> {
+        Timers::filter(&self.conn, filter)
+    }
+
+    /// A timer together with its project and tags, as `edit` shows it.
+    pub fn edit_timer(&self, rid: &str) -> AppResult<EditTimer> {
+        EditTimer::load(&self.conn, &self.timer(rid)?.rid)
+    }
+
+    /// The most recently started timer of a project.
+    pub fn last_project_timer(&self, name: &str) -> AppResult<Timer> {
+        Timer::last_for_project(&self.conn, self.project(name)?.id)
+    }
+
+    pub fn timer_project(&self, timer: &Timer) -> AppResult<Project> {
+        Project::for_timer(&self.conn, timer.id)
+    }
+
+    pub fn timer_tags(&self, timer: &Timer) -> AppResult<Tags> {
+        Tags::for_timer(&self.conn, timer.id)
+    }
+
+    /// A timer's segments, oldest first.
+    pub fn timer_segments(&self, timer: &Timer) -> AppResult<Segments> {
+        Segments::for_timer(&self.conn, timer.id)
+    }
+
+    /// When a running timer was paused, or `None` if it's running or stopped.
+    pub fn paused_since(
+        &self, timer: &Timer,
+    ) -> AppResult<Option<DateTime<Utc>>> {
+        timer.paused_since(&self.conn)
+    }
+
+    /// Maps every timer id to its project's name.
+    pub fn project_names_by_timer(&self) -> AppResult<HashMap<i32, String>> {
+        Projects::names_by_timer(&self.conn)
+    }
+
+    /// Maps every timer id to its tags' names.
+    pub fn tag_names_by_timer(&self) -> AppResult<HashMap<i32, Vec<String>>> {
+        Tags::names_by_timer(&self.conn)
+    }
+
+    /// Maps every timer id to its segments, oldest first.
+    pub fn segments_by_timer(&self) -> AppResult<HashMap<i32, Segments>> {
+        Segments::by_timer(&self.conn)
+    }
+
+    /// Every pair of timers that overlap each other, earliest first.
+    pub fn overlap_pairs(&self) -> AppResult<Vec<(Timer, Timer)>> {
+        Timers::overlap_pairs(&self.conn)
+    }
+
+    /// The timers that overlap `[start, end)`.
+    pub fn overlapping(
+        &self, start: DateTime<Utc>, end: Option<DateTime<Utc>>,
+    ) -> AppResult<Timers> {
+        Timers::overlapping(&self.conn, start, end, None)
+    }
+
+    /// Totals the timers matching `filter`, grouped by `group_by`.
+    pub fn report(
+        &self, config: &Config, filter: &TimerFilter, group_by: GroupBy,
+    ) -> AppResult<Report> {
+        Report::build(&self.conn, config, filter, group_by)
+    }
+
+    /// The timers matching `filter` as they're written to an export file,
+    /// with times in `tz`, or UTC without it.
+    pub fn export(
+        &self, filter: &TimerFilter, tz: Option<&Tz>,
+    ) -> AppResult<Vec<ExportedTimer>> {
+        ExportedTimer::load(&self.conn, filter, tz)
+    }
+
+    /// Starts a timer at `start`, first stopping every running timer when
+    /// `options.switch` is set. Returns the new timer and the stopped ones.
+    ///
+    /// Unless `options.allow_overlap` is set, a timer that would overlap
+    /// another one fails with `ErrorKind::Overlap` and leaves everything as it
+    /// was. The timers `switch` stops don't count.
+    pub fn start(
+        &mut self, project: &str, tags: &[String], note: Option<String>,
+        start: DateTime<Utc>, options: StartOptions,
+    ) -> AppResult<(Timer, Timers)> {
+        let create_timer = CreateTimer::new(start, None, note);
+        let stopped = self.atomically(|store| {
+            let mut stopped = Timers::default();
+            if options.switch {
+                stopped = Timers::currently_running(&store.conn)?;
+                stopped.stop_all(&store.conn, start)?;
+            }
+            if !options.allow_overlap {
+                Timers::check_overlaps(&store.conn, start, None, None)?;
+            }
+
+            db::insert_timer(&store.conn, project, tags, &create_timer)?;
+            Ok(stopped)
+        })?;
+
+        let started = self.timer(&create_timer.rid)?;
+        Ok((started, stopped))
+    }
+
+    /// Inserts a timer, creating its project and tags if they don't exist.
+    /// Overlaps are refused as in `start`, and so is a timer that ends before
+    /// it starts.
+    pub fn add(
+        &mut self, project: &str, tags: &[String], timer: &CreateTimer,
+        allow_overlap: bool,
+    ) -> AppResult<Timer> {
+        check_order(timer.start, timer.end)?;
+        self.atomically(|store| {
+            if !allow_overlap {
+                Timers::check_overlaps(
+                    &store.conn,
+                    timer.start,
+                    timer.end,
+                    None,
+                )?;
+            }
+            db::insert_timer(&store.conn, project, tags, timer)
+        })?;
+
+        self.timer(&timer.rid)
+    }
+
+    /// Saves a timer's times and note, and replaces its project and tags.
+    /// Overlaps with other timers are refused as in `start`, and so is an
+    /// `end` before `start`.
+    pub fn update_timer(
+        &mut self, timer: &Timer, project: &str, tags: &[String],
+        allow_overlap: bool,
+    ) -> AppResult<()> {
+        check_order(timer.start, timer.end)?;
+        self.atomically(|store| {
+            if !allow_overlap {
+                Timers::check_overlaps(
+                    &store.conn,
+                    timer.start,
+                    timer.end,
+                    Some(timer.id),
+                )?;
+            }
+            db::update_timer(&store.conn, timer, project, tags)
+        })
+    }
+
+    /// Cuts each of the `overlapping` timers back so it ends when `start`
+    /// begins, or starts when `end` is over, and returns them. A timer lying
+    /// entirely inside `[start, end)`, or spanning past both ends of it,
+    /// can't be trimmed, and then none of them are.
+    pub fn trim(
+        &mut self, overlapping: Timers, start: DateTime<Utc>,
+        end: Option<DateTime<Utc>>,
+    ) -> AppResult<Timers> {
+        let mut trimmed = Timers::default();
+        for mut timer in overlapping.0 {
+            let ends_after = match (timer.end, end) {
+                (_, None) => false,
+                (None, Some(_)) => true,
+                (Some(timer_end), Some(end)) => timer_end > end,
+            };
+
+            if timer.start < start && !ends_after {
+                timer.end = Some(start);
+            } else if timer.start >= start && ends_after {
+                timer.start = end.unwrap();
+            } else {
+                return Err(AppError::from_str(&format!(
+                    "Timer {} can't be trimmed around this one. Nothing was \
+                     changed.",
+                    timer.rid
+                )));
+            }
+
+            trimmed.0.push(timer);
+        }
+
+        self.atomically(|store| {
+            for timer in &trimmed.0 {
+                timer.update(&store.conn)?;
+            }
+            Ok(())
+        })?;
+
+        Ok(trimmed)
+    }
+
+    /// Stops a timer at `at`, or where it was paused.
+    pub fn stop(&self, timer: &mut Timer, at: DateTime<Utc>) -> AppResult<()> {
+        timer.stop(&self.conn, at)
+    }
+
+    pub fn pause(&self, timer: &Timer, at: DateTime<Utc>) -> AppResult<()> {
+        timer.pause(&self.conn, at)
+    }
+
+    pub fn resume(&self, timer: &Timer, at: DateTime<Utc>) -> AppResult<()> {
+        timer.resume(&self.conn, at)
+    }
+
+    pub fn delete_timer(&mut self, rid: &str) -> AppResult<Timer> {
+        db::delete_timer(&mut self.conn, rid)
+    }
+
+    /// Inserts exported timers, updating existing ones when `update` is set.
+    /// With `dry_run`, nothing is written. A timer that would overlap another
+    /// one fails the whole import, unless `allow_overlap` is set.
+    pub fn import(
+        &mut self, timers: Vec<ExportedTimer>, update: bool, dry_run: bool,
+        allow_overlap: bool,
+    ) -> AppResult<ImportSummary> {
+        import::import_timers(
+            &mut self.conn,
+            timers,
+            update,
+            dry_run,
+            allow_overlap,
+        )
+    }
+
+    /// The project and tags of the most recent timers, without repeats.
+    pub fn recent_combinations(
+        &self, count: usize,
+    ) -> AppResult<Vec<(String, Vec<String>)>> {
+        db::recent_combinations(&self.conn, count)
+    }
+
+    pub fn projects(&self) -> AppResult<Projects> {
+        Projects::all(&self.conn)
+    }
+
+    pub fn project(&self, name: &str) -> AppResult<Project> {
+        db::find_project(&self.conn, name)
+    }
+
+    pub fn project_timers(&self, name: &str) -> AppResult<Timers> {
+        Timers::for_project(&self.conn, self.project(name)?.id)
+    }
+
+    pub fn update_project(&self, project: &Project) -> AppResult<()> {
+        project.update(&self.conn)
+    }
+
+    /// Removes a project along with its timers, returning how many timers
+    /// were removed.
+    pub fn delete_project(&mut self, name: &str) -> AppResult<usize> {
+        db::delete_project(&mut self.conn, name)
+    }
+
+    /// Moves project `from`'s timers to `into` and removes `from`, returning
+    /// how many timers were moved.
+    pub fn merge_projects(
+        &mut self, from: &str, into: &str,
+    ) -> AppResult<usize> {
+        db::merge_projects(&mut self.conn, from, into)
+    }
+
+    pub fn tags(&self) -> AppResult<Tags> {
+        Tags::all(&self.conn)
+    }
+
+    pub fn tag(&self, name: &str) -> AppResult<Tag> {
+        db::find_tag(&self.conn, name)
+    }
+
+    pub fn tag_timers(&self, name: &str) -> AppResult<Timers> {
+        Timers::for_tag(&self.conn, self.tag(name)?.id)
+    }
+
+    pub fn update_tag(&self, tag: &Tag) -> AppResult<()> {
+        tag.update(&self.conn)
+    }
+
+    /// Removes a tag from its timers and deletes it, returning how many
+    /// timers had it.
+    pub fn delete_tag(&mut self, name: &str) -> AppResult<usize> {
+        db::delete_tag(&self.conn, name)
+    }
+
+    /// Moves tag `from` to `into` on every timer and removes `from`,
+    /// returning how many timers were retagged.
+    pub fn merge_tags(&mut self, from: &str, into: &str) -> AppResult<usize> {
+        db::merge_tags(&mut self.conn, from, into)
+    }
+}
+
+/// Refuses a timer that ends before it starts, which would otherwise be
+/// saved with a negative duration.
+fn check_order(
+    start: DateTime<Utc>, end: Option<DateTime<Utc>>,
+) -> AppResult<()> {
+    match end {
+        Some(end) if end < start => {
+            Err(AppError::from_str("A timer can't end before it starts."))
+        },
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,200 @@
+use std::{collections::HashMap, io::Write};
+
+use faramir::{errors::AppResult, models::project::Project, Store};
+use serde::{Deserialize, Serialize};
+use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+
+/// Colors handed out to projects and tags that don't have one configured.
+const PALETTE: [Color; 6] = [
+    Color::Blue,
+    Color::Green,
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::Red,
+];
+
+/// Picks a name's color: its configured `color` if termcolor understands it
+/// (e.g. `red`, `208` or `255,128,0`), and otherwise one derived from the
+/// name, so a project keeps its color from one run to the next.
+pub fn name_color(name: &str, configured: Option<&str>) -> Color {
+    if let Some(color) = configured.and_then(|c| c.parse::<Color>().ok()) {
+        return color;
+    }
+
+    let hash = name
+        .bytes()
+        .fold(5381u32, |hash, b| hash.wrapping_mul(33) ^ u32::from(b));
+    PALETTE[hash as usize % PALETTE.len()]
+}
+
+/// The configured colors of every project and tag. Serializable, so the
+/// daemon can send it to the CLI.
+#[derive(Deserialize, Serialize)]
+pub struct Palette {
+    projects: HashMap<String, Option<String>>,
+    tags:     HashMap<String, Option<String>>,
+}
+
+impl Palette {
+    pub fn load(store: &Store) -> AppResult<Self> {
+        let projects = store
+            .projects()?
+            .0
+            .into_iter()
+            .map(|p| (p.name, p.color))
+            .collect();
+        let tags =
+            store.tags()?.0.into_iter().map(|t| (t.name, t.color)).collect();
+
+        Ok(Palette { projects, tags })
+    }
+
+    pub fn project(&self, name: &str) -> Cell {
+        let configured = self.projects.get(name).and_then(|c| c.as_deref());
+        Cell::colored(name, name_color(name, configured))
+    }
+
+    /// The tags as one comma-separated cell, each tag in its own color.
+    pub fn tags(&self, names: &[String]) -> Cell {
+        let mut cell = Cell::plain("");
+        for (i, name) in names.iter().enumerate() {
+            if i > 0 {
+                cell.push(", ", ColorSpec::new());
+            }
+
+            let configured = self.tags.get(name).and_then(|c| c.as_deref());
+            let color = name_color(name, configured);
+            let mut spec = ColorSpec::new();
+            spec.set_fg(Some(color));
+            cell.push(name, spec);
+        }
+
+        cell
+    }
+}
+
+/// A project's name in its color. Archived projects are marked as such and
+/// left uncolored.
+pub fn project_cell(project: &Project) -> Cell {
+    match project.archived {
+        true => Cell::plain(&format!("{} (archived)", project.name)),
+        false => Cell::colored(
+            &project.name,
+            name_color(&project.name, project.color.as_deref()),
+        ),
+    }
+}
+
+/// A table cell, made of one or more differently colored pieces of text.
+pub struct Cell {
+    spans: Vec<(String, ColorSpec)>,
+}
+
+impl Cell {
+    pub fn plain(text: &str) -> Self {
+        Cell {
+            spans: vec![(text.into(), ColorSpec::new())],
+        }
+    }
+
+    pub fn colored(text: &str, color: Color) -> Self {
+        let mut spec = ColorSpec::new();
+        spec.set_fg(Some(color));
+
+        Cell {
+            spans: vec![(text.into(), spec)],
+        }
+    }
+
+    pub fn push(&mut self, text: &str, spec: ColorSpec) {
+        self.spans.push((text.into(), spec));
+    }
+
+    fn width(&self) -> usize {
+        self.spans.iter().map(|(text, _)| text.chars().count()).sum()
+    }
+}
+
+struct Row {
+    cells:     Vec<Cell>,
+    highlight: bool,
+}
+
+/// Rows of cells printed with aligned columns.
+pub struct Table {
+    header: Vec<&'static str>,
+    rows:   Vec<Row>,
+}
+
+impl Table {
+    pub fn new(header: Vec<&'static str>) -> Self {
+        Table {
+            header,
+            rows: vec![],
+        }
+    }
+
+    pub fn push(&mut self, cells: Vec<Cell>) {
+        self.rows.push(Row {
+            cells,
+            highlight: false,
+        });
+    }
+
+    /// Adds a row printed in bold, e.g. for a running timer.
+    pub fn push_highlighted(&mut self, cells: Vec<Cell>) {
+        self.rows.push(Row {
+            cells,
+            highlight: true,
+        });
+    }
+
+    pub fn print(&self, color: ColorChoice) -> AppResult<()> {
+        let mut widths = self
+            .header
+            .iter()
+            .map(|h| h.chars().count())
+            .collect::<Vec<usize>>();
+        for row in &self.rows {
+            for (i, cell) in row.cells.iter().enumerate() {
+                widths[i] = widths[i].max(cell.width());
+            }
+        }
+
+        let mut stdout = StandardStream::stdout(color);
+        let header = self
+            .header
+            .iter()
+            .map(|h| Cell::plain(h))
+            .collect::<Vec<Cell>>();
+        write_row(&mut stdout, &header, &widths, true)?;
+        for row in &self.rows {
+            write_row(&mut stdout, &row.cells, &widths, row.highlight)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write_row(
+    stdout: &mut StandardStream, cells: &[Cell], widths: &[usize], bold: bool,
+) -> AppResult<()> {
+    for (i, cell) in cells.iter().enumerate() {
+        for (text, spec) in &cell.spans {
+            let mut spec = spec.clone();
+            spec.set_bold(bold);
+            stdout.set_color(&spec)?;
+            write!(stdout, "{}", text)?;
+            stdout.reset()?;
+        }
+
+        let rest_is_empty = cells[i + 1..].iter().all(|c| c.width() == 0);
+        if !rest_is_empty {
+            write!(stdout, "{}", " ".repeat(widths[i] - cell.width() + 2))?;
+        }
+    }
+    writeln!(stdout)?;
+
+    Ok(())
+}
@@ -0,0 +1,388 @@
+use chrono::{
+    offset::TimeZone, DateTime, Datelike, Duration, NaiveDate, NaiveDateTime,
+    NaiveTime, Timelike, Utc, Weekday,
+};
+use chrono_tz::Tz;
+
+use crate::{
+    errors::{AppError, AppResult},
+    models::config::Config,
+};
+
+const ISO_FORMATS: [&str; 6] = [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%d %H:%M",
+];
+
+/// Parses a user-supplied point in time. Accepted, in order:
+///
+/// * the config's `time_format`, e.g. `2020/01/05 21:30:00`
+/// * `now`
+/// * ISO 8601, with or without an offset, e.g. `2020-01-05T21:30:00Z`,
+///   `2020-01-05 21:30` or `2020-01-05`
+/// * relative offsets, e.g. `10 minutes ago`, `in 1h`, `-1h30m`, `+15m`
+/// * a day name with an optional time, e.g. `yesterday 9:00`, `monday 2pm`.
+///   Weekdays mean the most recent one, today included.
+/// * a bare time of day, e.g. `17:30` or `9am`, which means today
+///
+/// Everything without an explicit offset is in the config's `timezone`.
+pub fn parse_time(input: &str, config: &Config) -> AppResult<DateTime<Utc>> {
+    let tz = config.tz()?;
+    parse_time_at(input, config, &tz, Utc::now())
+}
+
+fn parse_time_at(
+    input: &str, config: &Config, tz: &Tz, now: DateTime<Utc>,
+) -> AppResult<DateTime<Utc>> {
+    let input = input.trim();
+    let lower = input.to_lowercase();
+
+    if let Ok(dt) = tz.datetime_from_str(input, &config.time_format) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    if lower == "now" {
+        return Ok(now);
+    }
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(input) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+
+    for format in &ISO_FORMATS {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(input, format) {
+            return local(tz, naive);
+        }
+    }
+
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return local(tz, date.and_hms(0, 0, 0));
+    }
+
+    if let Some(dt) = parse_relative(&lower, now)? {
+        return Ok(dt);
+    }
+
+    let today = now.with_timezone(tz).date().naive_local();
+    let (day, rest) = match lower.find(' ') {
+        Some(i) => (&lower[..i], lower[i..].trim()),
+        None => (lower.as_str(), ""),
+    };
+
+    if let Some(date) = parse_day(day, today) {
+        let time = match rest {
+            "" => Some(NaiveTime::from_hms(0, 0, 0)),
+            rest => parse_time_of_day(rest),
+        };
+        if let Some(time) = time {
+            return local(tz, date.and_time(time));
+        }
+    }
+
+    if let Some(time) = parse_time_of_day(&lower) {
+        return local(tz, today.and_time(time));
+    }
+
+    Err(AppError::from_str(&format!(
+        "Unable to parse '{}' as a time. Tried your time_format ({}), \
+         ISO 8601 (2020-01-05T21:30:00Z, 2020-01-05 21:30), relative times \
+         (10 minutes ago, in 1h, -1h30m), day names (yesterday 9:00, monday \
+         2pm) and times of day (17:30, 9am).",
+        input, config.time_format
+    )))
+}
+
+/// Parses a duration made of one or more `<number><unit>` parts, e.g.
+/// `1h30m`, `90m`, `1.5h` or `1 hour 30 minutes`.
+pub fn parse_duration(input: &str) -> AppResult<Duration> {
+    let invalid = || {
+        AppError::from_str(&format!(
+            "Unable to parse '{}' as a duration. Use units like 2h15m, 90m, \
+             1.5h or '10 minutes'.",
+            input
+        ))
+    };
+
+    let mut chars = input.trim().chars().peekable();
+    let mut seconds = 0.0;
+    let mut found = false;
+
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        if chars.peek().is_none() {
+            break;
+        }
+
+        let mut number = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || *c == '.') {
+            number.push(c);
+        }
+
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+
+        let mut unit = String::new();
+        while let Some(c) = chars.next_if(|c| c.is_alphabetic()) {
+            unit.push(c);
+        }
+
+        let value: f64 = number.parse().map_err(|_| invalid())?;
+        seconds += value * unit_seconds(&unit).ok_or_else(invalid)?;
+        found = true;
+    }
+
+    if !found {
+        return Err(invalid());
+    }
+
+    let milliseconds = (seconds * 1000.0).round();
+    if milliseconds >= i64::MAX as f64 {
+        return Err(AppError::from_str(&format!(
+            "The duration '{}' is too long.",
+            input.trim()
+        )));
+    }
+
+    Ok(Duration::milliseconds(milliseconds as i64))
+}
+
+fn unit_seconds(unit: &str) -> Option<f64> {
+    match unit.to_lowercase().as_str() {
+        "s" | "sec" | "secs" | "second" | "seconds" => Some(1.0),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(60.0),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Some(3600.0),
+        "d" | "day" | "days" => Some(86400.0),
+        "w" | "week" | "weeks" => Some(604800.0),
+        _ => None,
+    }
+}
+
+/// `Ok(None)` when `input` isn't a relative time, and an error when it is
+/// one but lands outside the range of dates chrono can represent.
+fn parse_relative(
+    input: &str, now: DateTime<Utc>,
+) -> AppResult<Option<DateTime<Utc>>> {
+    let (rest, forward) = if let Some(rest) = input.strip_suffix(" ago") {
+        (rest, false)
+    } else if let Some(rest) = input.strip_prefix("in ") {
+        (rest, true)
+    } else if let Some(rest) = input.strip_prefix('-') {
+        (rest, false)
+    } else if let Some(rest) = input.strip_prefix('+') {
+        (rest, true)
+    } else {
+        return Ok(None);
+    };
+
+    let duration = match parse_duration(rest) {
+        Ok(duration) => duration,
+        Err(_) => return Ok(None),
+    };
+    let dt = match forward {
+        true => now.checked_add_signed(duration),
+        false => now.checked_sub_signed(duration),
+    };
+
+    match dt {
+        Some(dt) => Ok(Some(dt)),
+        None => Err(AppError::from_str(&format!(
+            "'{}' is too far from now.",
+            input
+        ))),
+    }
+}
+
+fn parse_day(day: &str, today: NaiveDate) -> Option<NaiveDate> {
+    let weekday = match day {
+        "today" => return Some(today),
+        "yesterday" => return Some(today.pred()),
+        "tomorrow" => return Some(today.succ()),
+        "mon" | "monday" => Weekday::Mon,
+        "tue" | "tues" | "tuesday" => Weekday::Tue,
+        "wed" | "wednesday" => Weekday::Wed,
+        "thu" | "thurs" | "thursday" => Weekday::Thu,
+        "fri" | "friday" => Weekday::Fri,
+        "sat" | "saturday" => Weekday::Sat,
+        "sun" | "sunday" => Weekday::Sun,
+        _ => return None,
+    };
+
+    let days_back = (7 + today.weekday().num_days_from_monday()
+        - weekday.num_days_from_monday())
+        % 7;
+    Some(today - Duration::days(days_back as i64))
+}
+
+/// Parses `17:30`, `17:30:15`, `9am`, `9:15 pm`, etc.
+fn parse_time_of_day(input: &str) -> Option<NaiveTime> {
+    let (time, meridiem) = if let Some(rest) = input.strip_suffix("am") {
+        (rest.trim(), Some(0))
+    } else if let Some(rest) = input.strip_suffix("pm") {
+        (rest.trim(), Some(12))
+    } else {
+        (input, None)
+    };
+
+    let parsed = NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .ok();
+
+    match meridiem {
+        None => parsed,
+        Some(offset) => {
+            let (hour, minute, second) = match parsed {
+                Some(t) => (t.hour(), t.minute(), t.second()),
+                None => (time.parse::<u32>().ok()?, 0, 0),
+            };
+            if hour == 0 || hour > 12 {
+                return None;
+            }
+
+            NaiveTime::from_hms_opt(hour % 12 + offset, minute, second)
+        },
+    }
+}
+
+fn local(tz: &Tz, naive: NaiveDateTime) -> AppResult<DateTime<Utc>> {
+    match tz.from_local_datetime(&naive).earliest() {
+        Some(dt) => Ok(dt.with_timezone(&Utc)),
+        None => Err(AppError::from_str(&format!(
+            "{} doesn't exist in timezone {}.",
+            naive,
+            tz.name()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wednesday 2020-01-08, 10:00 in America/New_York.
+    fn now() -> DateTime<Utc> {
+        Utc.ymd(2020, 1, 8).and_hms(15, 0, 0)
+    }
+
+    fn parse(input: &str) -> AppResult<DateTime<Utc>> {
+        let config = Config::default();
+        parse_time_at(input, &config, &config.tz().unwrap(), now())
+    }
+
+    /// A wall-clock time in America/New_York (EST, UTC-5) as UTC.
+    fn est(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.ymd(y, m, d).and_hms(h, min, 0) + Duration::hours(5)
+    }
+
+    #[test]
+    fn time_format_and_now() {
+        assert_eq!(
+            parse("2020/01/05 21:30:00").unwrap(),
+            Utc.ymd(2020, 1, 6).and_hms(2, 30, 0)
+        );
+        assert_eq!(parse("now").unwrap(), now());
+        assert_eq!(parse(" NOW ").unwrap(), now());
+    }
+
+    #[test]
+    fn iso() {
+        assert_eq!(
+            parse("2020-01-05T21:30:00Z").unwrap(),
+            Utc.ymd(2020, 1, 5).and_hms(21, 30, 0)
+        );
+        assert_eq!(
+            parse("2020-01-05T21:30:00+01:00").unwrap(),
+            Utc.ymd(2020, 1, 5).and_hms(20, 30, 0)
+        );
+        assert_eq!(parse("2020-01-05 09:30").unwrap(), est(2020, 1, 5, 9, 30));
+        assert_eq!(parse("2020-01-05T09:30").unwrap(), est(2020, 1, 5, 9, 30));
+        assert_eq!(parse("2020-01-05").unwrap(), est(2020, 1, 5, 0, 0));
+    }
+
+    #[test]
+    fn relative() {
+        assert_eq!(
+            parse("10 minutes ago").unwrap(),
+            now() - Duration::minutes(10)
+        );
+        assert_eq!(parse("in 1h").unwrap(), now() + Duration::hours(1));
+        assert_eq!(parse("-1h30m").unwrap(), now() - Duration::minutes(90));
+        assert_eq!(parse("+15m").unwrap(), now() + Duration::minutes(15));
+        assert_eq!(parse("2 days ago").unwrap(), now() - Duration::days(2));
+    }
+
+    #[test]
+    fn relative_out_of_range() {
+        assert!(parse("1000000000w ago").is_err());
+        assert!(parse("in 1000000000w").is_err());
+        assert!(parse("-99999999999999999999h").is_err());
+    }
+
+    #[test]
+    fn days() {
+        assert_eq!(parse("today").unwrap(), est(2020, 1, 8, 0, 0));
+        assert_eq!(parse("yesterday 9:00").unwrap(), est(2020, 1, 7, 9, 0));
+        assert_eq!(parse("tomorrow 8am").unwrap(), est(2020, 1, 9, 8, 0));
+        assert_eq!(parse("monday 2pm").unwrap(), est(2020, 1, 6, 14, 0));
+        assert_eq!(parse("Thu").unwrap(), est(2020, 1, 2, 0, 0));
+    }
+
+    #[test]
+    fn weekdays_include_today() {
+        assert_eq!(parse("wednesday 9:00").unwrap(), est(2020, 1, 8, 9, 0));
+    }
+
+    #[test]
+    fn bare_times() {
+        assert_eq!(parse("17:30").unwrap(), est(2020, 1, 8, 17, 30));
+        assert_eq!(
+            parse("17:30:15").unwrap(),
+            est(2020, 1, 8, 17, 30) + Duration::seconds(15)
+        );
+    }
+
+    #[test]
+    fn am_pm() {
+        assert_eq!(parse("9am").unwrap(), est(2020, 1, 8, 9, 0));
+        assert_eq!(parse("9:15 pm").unwrap(), est(2020, 1, 8, 21, 15));
+        assert_eq!(parse("12am").unwrap(), est(2020, 1, 8, 0, 0));
+        assert_eq!(parse("12pm").unwrap(), est(2020, 1, 8, 12, 0));
+        assert!(parse("13pm").is_err());
+        assert!(parse("0am").is_err());
+    }
+
+    #[test]
+    fn rejected() {
+        assert!(parse("").is_err());
+        assert!(parse("soon").is_err());
+        assert!(parse("25:00").is_err());
+        assert!(parse("in a while").is_err());
+        assert!(parse("2020-13-01").is_err());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("90m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("1.5h").unwrap(), Duration::minutes(90));
+        assert_eq!(
+            parse_duration("1 hour 30 minutes").unwrap(),
+            Duration::minutes(90)
+        );
+        assert_eq!(parse_duration("2d").unwrap(), Duration::days(2));
+        assert_eq!(parse_duration("1w").unwrap(), Duration::weeks(1));
+        assert_eq!(parse_duration("45 SECS").unwrap(), Duration::seconds(45));
+    }
+
+    #[test]
+    fn rejected_durations() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("1").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("1x").is_err());
+        assert!(parse_duration("1.2.3h").is_err());
+        assert!(parse_duration("99999999999999999999w").is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use chrono::{offset::TimeZone, DateTime, Utc};
+use chrono_tz::Tz;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 
 pub fn rand_string(len: usize) -> String {
@@ -17,6 +19,11 @@ pub fn format_seconds(secs: i64) -> String {
     )
 }
 
+/// Formats a stored UTC time in the user's timezone.
+pub fn format_time(dt: &DateTime<Utc>, tz: &Tz, format: &str) -> String {
+    tz.from_utc_datetime(&dt.naive_utc()).format(format).to_string()
+}
+
 pub fn parse_tags(tags: Option<&str>) -> Option<Vec<String>> {
     match tags {
         Some(tags_string) => {
@@ -0,0 +1,141 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{offset::TimeZone, DateTime, FixedOffset, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    errors::{AppError, AppResult},
+    export::ExportedTimer,
+};
+
+/// Watson's running timer, as stored in its `state` file. The file holds an
+/// empty object when nothing is running.
+#[derive(Debug, Deserialize)]
+struct State {
+    project: Option<String>,
+    start:   Option<f64>,
+    #[serde(default)]
+    tags:    Vec<String>,
+}
+
+/// Watson's data directory: `$WATSON_DIR`, or `watson/` in the config
+/// directory.
+pub fn default_dir() -> PathBuf {
+    if let Ok(watson_dir) = env::var("WATSON_DIR") {
+        return PathBuf::from(watson_dir);
+    }
+
+    match env::var("XDG_CONFIG_HOME") {
+        Ok(xdg_path) => PathBuf::from(xdg_path).join("watson"),
+        Err(_) => match env::var("HOME") {
+            Ok(home_path) => Path::new(&home_path).join(".config/watson"),
+            Err(_) => PathBuf::from("watson"),
+        },
+    }
+}
+
+/// Reads Watson's `frames` and `state` files. `path` is either the Watson
+/// directory or the `frames` file itself.
+///
+/// Frames keep their Watson id as the rid, so importing twice is a no-op. A
+/// running timer from `state` gets an rid derived from its start time, see
+/// `state_rid`.
+pub fn read(path: &Path) -> AppResult<Vec<ExportedTimer>> {
+    let (frames_path, state_path) = match path.is_dir() {
+        true => (path.join("frames"), path.join("state")),
+        false => (path.to_path_buf(), path.with_file_name("state")),
+    };
+
+    let content = fs::read_to_string(&frames_path)?;
+    let frames: Vec<Vec<Value>> = serde_json::from_str(&content)?;
+
+    let mut timers = vec![];
+    for frame in frames {
+        timers.push(frame_to_timer(&frame)?);
+    }
+
+    if state_path.exists() {
+        let content = fs::read_to_string(&state_path)?;
+        let state: State = serde_json::from_str(&content)?;
+
+        if let (Some(project), Some(start)) = (state.project, state.start) {
+            let start = from_timestamp(start).ok_or_else(|| {
+                AppError::from_str(&format!(
+                    "Invalid start time in Watson's state: {}",
+                    start
+                ))
+            })?;
+            timers.push(ExportedTimer {
+                rid:      state_rid(&start),
+                start,
+                end:      None,
+                duration: 0,
+                note:     None,
+                project:  Some(project),
+                tags:     state.tags,
+                segments: vec![],
+            });
+        }
+    }
+
+    Ok(timers)
+}
+
+/// The rid of a timer imported from Watson's `state`. Once Watson stops it,
+/// the frame it saves has a real id, and `import::import_timers` finds the
+/// running timer through this rid to stop it instead of adding the frame
+/// again.
+pub fn state_rid(start: &DateTime<FixedOffset>) -> String {
+    format!("watson-state-{}", start.timestamp())
+}
+
+/// Frames are arrays of `[start, stop, project, id, tags, updated_at]`,
+/// optionally followed by a note in newer Watson versions.
+fn frame_to_timer(frame: &[Value]) -> AppResult<ExportedTimer> {
+    let invalid = || {
+        AppError::from_str(&format!("Invalid Watson frame: {:?}", frame))
+    };
+
+    let start = frame.first().and_then(Value::as_f64).ok_or_else(invalid)?;
+    let stop = frame.get(1).and_then(Value::as_f64).ok_or_else(invalid)?;
+    let project = frame.get(2).and_then(Value::as_str).ok_or_else(invalid)?;
+    let id = frame.get(3).and_then(Value::as_str).ok_or_else(invalid)?;
+    let tags = match frame.get(4).and_then(Value::as_array) {
+        Some(tags) => tags
+            .iter()
+            .filter_map(Value::as_str)
+            .map(|t| t.into())
+            .collect(),
+        None => vec![],
+    };
+    let note = frame
+        .get(6)
+        .and_then(Value::as_str)
+        .filter(|n| !n.is_empty())
+        .map(|n| n.into());
+
+    Ok(ExportedTimer {
+        rid: id.into(),
+        start: from_timestamp(start).ok_or_else(invalid)?,
+        end: Some(from_timestamp(stop).ok_or_else(invalid)?),
+        duration: (stop - start) as i64,
+        note,
+        project: Some(project.into()),
+        tags,
+        segments: vec![],
+    })
+}
+
+/// `None` when `timestamp` is out of chrono's range.
+fn from_timestamp(timestamp: f64) -> Option<DateTime<FixedOffset>> {
+    let secs = timestamp.floor();
+    let nanos = ((timestamp - secs) * 1e9) as u32;
+
+    Utc.timestamp_opt(secs as i64, nanos)
+        .single()
+        .map(|dt| dt.with_timezone(&FixedOffset::east(0)))
+}